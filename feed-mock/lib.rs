@@ -0,0 +1,314 @@
+use std::{net::SocketAddr, sync::{Arc, Mutex}};
+use bytes::Bytes;
+use futures_util::{StreamExt, SinkExt};
+use tokio::{spawn, select, io::{self, AsyncWriteExt}, net::{TcpListener, TcpStream, tcp::{OwnedReadHalf, OwnedWriteHalf}}, task::{JoinHandle, JoinSet}, time::{sleep_until, Duration, Instant}};
+use tokio_util::codec::FramedRead;
+use tokio_tungstenite::{WebSocketStream, tungstenite::protocol::Message};
+use livekit_feed::{package::{Package, FrameDecoder}, schema::InitRequest, stream::{Transport, HostInfo, TCP_BUFFER_SIZE}};
+
+/// One action of a script, run in order after the init response.
+#[derive(Clone, Debug)]
+pub enum Step {
+    Send(Package),
+    /// Sends the bytes as one frame as-is, for malformed or truncated packages.
+    Raw(Vec<u8>),
+    /// Waits before the next step, heartbeats are still answered meanwhile.
+    Sleep(Duration),
+    /// Stops sending anything, including heartbeat responses, but keeps the connection open.
+    Stall,
+    Disconnect,
+}
+
+/// What the server does on one connection.
+#[derive(Clone, Debug)]
+pub struct Script {
+    /// Code of the init response, `None` to never reply. The connection is closed after a non-zero code.
+    pub init_code: Option<i32>,
+    /// Sent in every heartbeat response.
+    pub popularity: u32,
+    pub steps: Vec<Step>,
+}
+
+impl Default for Script {
+    fn default() -> Script {
+        Script { init_code: Some(0), popularity: 1, steps: Vec::new() }
+    }
+}
+
+impl Script {
+    pub fn new(steps: Vec<Step>) -> Script {
+        Script { steps, ..Script::default() }
+    }
+
+    /// Rejects the init request with `code`, like the server does for an invalid token.
+    pub fn reject(code: i32) -> Script {
+        Script { init_code: Some(code), ..Script::default() }
+    }
+}
+
+// region: conn
+
+enum Conn {
+    Tcp(FramedRead<OwnedReadHalf, FrameDecoder>, OwnedWriteHalf),
+    // boxed, the websocket state is large
+    Ws(Box<WebSocketStream<TcpStream>>),
+}
+
+impl Conn {
+    async fn accept(transport: Transport, socket: TcpStream) -> io::Result<Conn> {
+        Ok(match transport {
+            Transport::Tcp => {
+                let (rx, tx) = socket.into_split();
+                Conn::Tcp(FramedRead::with_capacity(rx, FrameDecoder::default(), TCP_BUFFER_SIZE), tx)
+            },
+            Transport::Ws => Conn::Ws(Box::new(tokio_tungstenite::accept_async(socket).await.map_err(io::Error::other)?)),
+            Transport::Wss => unreachable!(),
+        })
+    }
+
+    /// Next frame from the client, `None` once it is gone or sent something undecodable.
+    async fn recv(&mut self) -> Option<Bytes> {
+        match self {
+            Conn::Tcp(rx, _) => rx.next().await?.ok(),
+            Conn::Ws(ws) => loop {
+                match ws.next().await?.ok()? {
+                    Message::Binary(frame) => break Some(frame.into()),
+                    Message::Close(_) => break None,
+                    _ => { },
+                }
+            },
+        }
+    }
+
+    async fn send(&mut self, frame: Vec<u8>) -> io::Result<()> {
+        match self {
+            Conn::Tcp(_, tx) => tx.write_all(&frame).await,
+            Conn::Ws(ws) => ws.send(Message::Binary(frame)).await.map_err(io::Error::other),
+        }
+    }
+
+    async fn send_package(&mut self, package: Package) -> io::Result<()> {
+        self.send(package.encode().map_err(io::Error::other)?).await
+    }
+
+    async fn close(self) -> io::Result<()> {
+        match self {
+            Conn::Tcp(_, mut tx) => tx.shutdown().await,
+            Conn::Ws(mut ws) => ws.as_mut().close(None).await.map_err(io::Error::other),
+        }
+    }
+}
+
+// endregion
+
+async fn serve(mut conn: Conn, script: Script, init_requests: Arc<Mutex<Vec<InitRequest>>>) -> io::Result<()> {
+    let Some(init) = conn.recv().await else { return Ok(()) };
+    if let Ok(Package::InitRequest(init)) = Package::decode(&init) {
+        if let Ok(init) = serde_json::from_str(&init) {
+            init_requests.lock().unwrap().push(init);
+        }
+    }
+
+    let mut stalled = true;
+    if let Some(code) = script.init_code {
+        conn.send_package(Package::InitResponse(format!("{{\"code\":{}}}", code))).await?;
+        if code != 0 {
+            return conn.close().await;
+        }
+        stalled = false;
+    }
+
+    let mut steps = script.steps.into_iter();
+    loop {
+        // run steps until one has to wait
+        let wait = if stalled { None } else {
+            loop {
+                match steps.next() {
+                    Some(Step::Send(package)) => conn.send_package(package).await?,
+                    Some(Step::Raw(frame)) => conn.send(frame).await?,
+                    Some(Step::Sleep(duration)) => break Some(Instant::now() + duration),
+                    Some(Step::Stall) => {
+                        stalled = true;
+                        break None;
+                    },
+                    Some(Step::Disconnect) => return conn.close().await,
+                    None => break None,
+                }
+            }
+        };
+
+        // keep reading meanwhile, to answer heartbeats and notice the client going away
+        loop {
+            select! {
+                frame = conn.recv() => match frame {
+                    Some(frame) => if !stalled && matches!(Package::decode(&frame), Ok(Package::HeartbeatRequest)) {
+                        conn.send_package(Package::HeartbeatResponse(script.popularity)).await?;
+                    },
+                    None => return Ok(()),
+                },
+                // the future is created even when the branch is disabled
+                _ = sleep_until(wait.unwrap_or_else(Instant::now)), if wait.is_some() => break,
+            }
+        }
+    }
+}
+
+/// Local server speaking the feed protocol, for testing clients without the real servers.
+pub struct MockServer {
+    addr: SocketAddr,
+    init_requests: Arc<Mutex<Vec<InitRequest>>>,
+    handle: JoinHandle<()>,
+}
+
+impl MockServer {
+    /// Listens on a random local port. The n-th connection runs the n-th script, later ones run the last script.
+    /// There is no TLS, so `Transport::Wss` is not supported.
+    pub async fn bind(transport: Transport, scripts: Vec<Script>) -> io::Result<MockServer> {
+        assert!(!scripts.is_empty());
+        if transport == Transport::Wss {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "mock server does not support wss"));
+        }
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let init_requests = Arc::new(Mutex::new(Vec::new()));
+        let received = init_requests.clone();
+        let handle = spawn(async move {
+            // dropped together with the accept loop, which aborts all connections
+            let mut conns = JoinSet::new();
+            let mut scripts = scripts.into_iter();
+            let mut script = scripts.next().unwrap();
+            loop {
+                let (socket, peer) = match listener.accept().await {
+                    Ok(accepted) => accepted,
+                    Err(err) => {
+                        log::warn!("(mock) accept error: {:?}", err);
+                        continue;
+                    },
+                };
+                let this = match scripts.next() {
+                    Some(next) => std::mem::replace(&mut script, next),
+                    None => script.clone(),
+                };
+                let init_requests = received.clone();
+                conns.spawn(async move {
+                    let result = match Conn::accept(transport, socket).await {
+                        Ok(conn) => serve(conn, this, init_requests).await,
+                        Err(err) => Err(err),
+                    };
+                    if let Err(err) = result {
+                        log::debug!("(mock) {} closed with error: {:?}", peer, err);
+                    }
+                });
+                // reap finished connections
+                while conns.try_join_next().is_some() { }
+            }
+        });
+        Ok(MockServer { addr, init_requests, handle })
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Host entry pointing every transport at this server.
+    pub fn host_info(&self) -> HostInfo {
+        let port = self.addr.port();
+        HostInfo { host: self.addr.ip().to_string(), port, ws_port: port, wss_port: port }
+    }
+
+    /// Init requests received so far, in connection order.
+    pub fn init_requests(&self) -> Vec<InitRequest> {
+        self.init_requests.lock().unwrap().clone()
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_util::StreamExt;
+    use livekit_feed::stream::{AnyFeedStream, FeedStreamError, StreamConfig, Credential};
+    use super::*;
+
+    macro_rules! connect {
+        ($server:expr, $transport:expr) => {
+            connect!($server, $transport, StreamConfig::default())
+        };
+        ($server:expr, $transport:expr, $config:expr) => {
+            AnyFeedStream::connect_with(&$config, &$server.host_info(), $transport, 1, Credential::guest(None, Some("token".to_owned()))).await
+        };
+    }
+
+    macro_rules! next {
+        ($stream:expr) => {
+            Package::decode(&$stream.next().await.unwrap().unwrap().payload).unwrap()
+        };
+    }
+
+    #[tokio::test]
+    async fn script() {
+        for transport in [Transport::Tcp, Transport::Ws] {
+            let server = MockServer::bind(transport, vec![Script::new(vec![
+                Step::Send(Package::Json("{}".to_owned())),
+                Step::Sleep(Duration::from_millis(300)),
+                Step::Send(Package::Multi(vec![Package::Json("{}".to_owned()); 2])),
+            ])]).await.unwrap();
+            let config = StreamConfig { heartbeat_interval: Duration::from_millis(100), ..StreamConfig::default() };
+            let mut stream = connect!(server, transport, config).unwrap();
+            assert_eq!(next!(stream), Package::InitResponse("{\"code\":0}".to_owned()));
+            assert_eq!(next!(stream), Package::Json("{}".to_owned()));
+            // answered while the script sleeps
+            let mut heartbeats = 0;
+            let last = loop {
+                match next!(stream) {
+                    Package::HeartbeatResponse(1) => heartbeats += 1,
+                    package => break package,
+                }
+            };
+            assert!(heartbeats >= 2);
+            assert_eq!(last, Package::Multi(vec![Package::Json("{}".to_owned()); 2]));
+            let init = &server.init_requests()[0];
+            assert_eq!((init.roomid, init.key.as_deref()), (1, Some("token")));
+        }
+    }
+
+    #[tokio::test]
+    async fn faults() {
+        let server = MockServer::bind(Transport::Tcp, vec![Script::reject(-101)]).await.unwrap();
+        assert!(matches!(connect!(server, Transport::Tcp), Err(FeedStreamError::InitRejected(-101))));
+
+        let server = MockServer::bind(Transport::Tcp, vec![Script { init_code: None, ..Script::default() }]).await.unwrap();
+        let config = StreamConfig { init_timeout: Duration::from_millis(100), ..StreamConfig::default() };
+        assert!(matches!(connect!(server, Transport::Tcp, config), Err(FeedStreamError::InitTimeout)));
+
+        let server = MockServer::bind(Transport::Tcp, vec![Script::new(vec![Step::Stall])]).await.unwrap();
+        let config = StreamConfig { idle_timeout: Some(Duration::from_millis(200)), ..StreamConfig::default() };
+        let mut stream = connect!(server, Transport::Tcp, config).unwrap();
+        assert!(stream.next().await.unwrap().is_ok());
+        assert!(matches!(stream.next().await, Some(Err(FeedStreamError::IdleTimeout))));
+
+        let server = MockServer::bind(Transport::Tcp, vec![Script::new(vec![Step::Raw(vec![0; 4])])]).await.unwrap();
+        let mut stream = connect!(server, Transport::Tcp).unwrap();
+        assert!(stream.next().await.unwrap().is_ok());
+        assert!(matches!(stream.next().await, Some(Err(FeedStreamError::PackageCodecError(_)))));
+    }
+
+    #[tokio::test]
+    async fn reconnect() {
+        let server = MockServer::bind(Transport::Ws, vec![
+            Script::new(vec![Step::Disconnect]),
+            Script::new(vec![Step::Send(Package::Json("{}".to_owned()))]),
+        ]).await.unwrap();
+        let mut stream = connect!(server, Transport::Ws).unwrap();
+        assert!(stream.next().await.unwrap().is_ok());
+        assert!(stream.next().await.is_none());
+        let mut stream = connect!(server, Transport::Ws).unwrap();
+        assert!(stream.next().await.unwrap().is_ok());
+        assert_eq!(next!(stream), Package::Json("{}".to_owned()));
+        assert_eq!(server.init_requests().len(), 2);
+    }
+}
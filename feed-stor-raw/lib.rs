@@ -1,10 +1,11 @@
-use std::path::PathBuf;
+use std::{path::PathBuf, sync::atomic::{AtomicU64, Ordering}};
 use bytes::Bytes;
 pub use crc32fast::hash as crc32;
 use foundations::{byterepr_struct, byterepr::ByteRepr};
 pub use kvdump;
 use kvdump::{KV, Sizes, Result, actor::{Request, WriterContextConfig}};
-use livekit_feed::stream::{Payload, now};
+use serde::{Serialize, Deserialize};
+use livekit_feed::{stream::{Payload, Transport}, clock::{Clock, SystemClock}};
 
 type WriterContext = kvdump::actor::WriterContext<Config, FILE_SYNC_INTERVAL_COUNT>;
 type Handle = tokio_actor::Handle<WriterContext>;
@@ -12,6 +13,8 @@ type Handle = tokio_actor::Handle<WriterContext>;
 pub const IDENT: &str = "livekit-feed-raw";
 pub const SIZES: Sizes = Sizes { scope: Some(4), key: Some(12), value: None };
 pub const FILE_SYNC_INTERVAL_COUNT: u16 = 500;
+/// Scope of `SessionRecord` rows, not a valid roomid.
+pub const SESSION_SCOPE: [u8; 4] = u32::MAX.to_be_bytes();
 
 pub struct Config;
 
@@ -25,6 +28,9 @@ impl kvdump::Config for Config {
     }
 }
 
+// Only the wall clock time is kept, as the key size is fixed by existing files. Rows are
+// appended in receiving order, so frames in the same millisecond keep their order anyway.
+// The rest of the payload metadata is kept in `SessionRecord`s.
 byterepr_struct! {
     #[derive(Debug)]
     pub struct Key {
@@ -42,6 +48,36 @@ impl Key {
     }
 }
 
+/// Json value of a row in `SESSION_SCOPE`, written before the first frame of each connection.
+/// Frames of the room that follow belong to this session until the next record of the room,
+/// and their `Payload::seq` is their position among them.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SessionRecord {
+    pub roomid: u32,
+    /// Unique within one file.
+    pub session: u64,
+    /// Wall clock time (ms) of connecting, also the time of the row key.
+    pub time: u64,
+    /// Monotonic time (µs) of the first frame, comparable between sessions of the same file.
+    pub time_mono: u64,
+    pub transport: Transport,
+    pub host: String,
+}
+
+impl SessionRecord {
+    pub fn from_payload(roomid: u32, payload: &Payload) -> SessionRecord {
+        let session = &payload.session;
+        SessionRecord {
+            roomid,
+            session: session.id,
+            time: session.time,
+            time_mono: payload.time_mono,
+            transport: session.transport,
+            host: session.host.clone(),
+        }
+    }
+}
+
 pub struct Writer {
     tx: Handle,
 }
@@ -49,6 +85,8 @@ pub struct Writer {
 pub struct RoomWriter {
     roomid: u32,
     roomid_bytes: Bytes,
+    // session ids start at 1
+    last_session: AtomicU64,
     tx: Handle,
 }
 
@@ -58,16 +96,21 @@ pub struct CloseHandle {
 
 impl Writer {
     pub async fn open(path: PathBuf) -> Result<(Writer, CloseHandle)> {
+        Writer::open_with_clock(path, &SystemClock).await
+    }
+
+    /// The file is named after the current time of `clock`.
+    pub async fn open_with_clock(path: PathBuf, clock: &dyn Clock) -> Result<(Writer, CloseHandle)> {
         tokio::fs::create_dir_all(&path).await?;
         let tx = tokio_actor::spawn_async(WriterContextConfig {
-            path: path.join(now().to_string()),
+            path: path.join(clock.now().to_string()),
             config: Config,
         }).await?;
         Ok((Writer { tx: tx.clone() }, CloseHandle { tx }))
     }
 
     pub fn open_room(&self, roomid: u32) -> RoomWriter {
-        RoomWriter { roomid, roomid_bytes: Bytes::copy_from_slice(&roomid.to_be_bytes()), tx: self.tx.clone() }
+        RoomWriter { roomid, roomid_bytes: Bytes::copy_from_slice(&roomid.to_be_bytes()), last_session: AtomicU64::new(0), tx: self.tx.clone() }
     }
 
     pub async fn write_hash(&self) -> Result<()> {
@@ -85,14 +128,31 @@ impl RoomWriter {
     }
 
     pub async fn insert_payload(&self, payload: &Payload) -> std::result::Result<(), String> {
+        if self.last_session.swap(payload.session.id, Ordering::Relaxed) != payload.session.id {
+            self.insert_session(payload).await?;
+        }
         let key = Key::from_payload(payload);
         self.tx.request(Request::KV(KV {
             scope: self.roomid_bytes.clone(),
             key: Bytes::copy_from_slice(&key.to_bytes()),
             value: payload.payload.clone(),
         })).await.map_err(|err| format!(
-            "[{: >10}] (stor-raw) FATAL: insert error: {:?} key={:?} val(hex)={}",
-            self.roomid, err, key, hex::encode(&payload.payload),
+            "[{: >10}] (stor-raw) FATAL: insert error: {:?} key={:?} session={} seq={} val(hex)={}",
+            self.roomid, err, key, payload.session.id, payload.seq, hex::encode(&payload.payload),
+        ))
+    }
+
+    async fn insert_session(&self, payload: &Payload) -> std::result::Result<(), String> {
+        let record = SessionRecord::from_payload(self.roomid, payload);
+        let value = Bytes::from(serde_json::to_vec(&record).unwrap());
+        let key = Key { time: record.time, hash: crc32(&value) };
+        self.tx.request(Request::KV(KV {
+            scope: Bytes::from_static(&SESSION_SCOPE),
+            key: Bytes::copy_from_slice(&key.to_bytes()),
+            value,
+        })).await.map_err(|err| format!(
+            "[{: >10}] (stor-raw) FATAL: insert error: {:?} session={:?}",
+            self.roomid, err, record,
         ))
     }
 }
@@ -103,3 +163,48 @@ impl CloseHandle {
         self.tx.wait_close().await
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{fs::{self, File}, sync::Arc};
+    use kvdump::Row;
+    use livekit_feed::{clock::FakeClock, stream::Session};
+    use super::*;
+
+    #[tokio::test]
+    async fn fake_clock() {
+        let path = std::env::temp_dir().join(format!("{}-test-{}", IDENT, std::process::id()));
+        let clock = FakeClock::new(1631676810000);
+        let (writer, close) = Writer::open_with_clock(path.clone(), clock.as_ref()).await.unwrap();
+        let room = writer.open_room(10308958);
+        let session = Arc::new(Session { id: 1, time: clock.now(), transport: Transport::Tcp, host: "127.0.0.1:2243".to_owned() });
+        for seq in 0..2 {
+            clock.advance(1000);
+            let payload = Payload { time: clock.now(), time_mono: clock.now_mono(), seq, session: session.clone(), payload: Bytes::from_static(b"{}") };
+            room.insert_payload(&payload).await.unwrap();
+        }
+        drop((room, writer));
+        close.wait_close().await.unwrap();
+
+        // named after the time of opening
+        let reader = kvdump::Reader::init(File::open(path.join("1631676810000")).unwrap()).unwrap();
+        let rows: Vec<KV> = reader.filter_map(|row| match row.unwrap() {
+            Row::KV(kv) => Some(kv),
+            Row::Hash(_) | Row::End => None,
+        }).collect();
+        assert_eq!(rows.len(), 3);
+
+        assert_eq!(rows[0].scope.as_ref(), SESSION_SCOPE);
+        let record: SessionRecord = serde_json::from_slice(&rows[0].value).unwrap();
+        assert_eq!((record.roomid, record.session, record.time, record.time_mono), (10308958, 1, 1631676810000, 1000000));
+        assert_eq!(record.transport, Transport::Tcp);
+
+        let times: Vec<u64> = rows[1..].iter().map(|kv| {
+            assert_eq!(kv.scope.as_ref(), 10308958u32.to_be_bytes());
+            Key::from_bytes(kv.key.as_ref().try_into().unwrap()).time
+        }).collect();
+        assert_eq!(times, [1631676811000, 1631676812000]);
+
+        fs::remove_dir_all(path).unwrap();
+    }
+}
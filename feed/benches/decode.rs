@@ -0,0 +1,35 @@
+use bytes::Bytes;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use livekit_feed::package::{Package, BytesPackage, Compression};
+
+const DANMAKU: &str = include_str!("../fixtures/danmaku.json");
+
+fn frames() -> Vec<(&'static str, Vec<u8>)> {
+    let json = Package::Json(DANMAKU.to_owned());
+    let json_large = Package::Json(format!("[{}]", [DANMAKU; 24].join(",")));
+    let multi = Package::Multi(vec![json.clone(); 32]);
+    vec![
+        ("json", json.encode().unwrap()),
+        ("json-large", json_large.encode().unwrap()),
+        ("multi-zlib", multi.clone().encode_with(Compression::Zlib).unwrap()),
+        ("multi-brotli", multi.encode_with(Compression::Brotli).unwrap()),
+    ]
+}
+
+fn decode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("decode");
+    for (name, frame) in frames() {
+        let bytes = Bytes::from(frame.clone());
+        group.throughput(Throughput::Bytes(frame.len() as u64));
+        group.bench_with_input(BenchmarkId::new("Package", name), &frame, |b, frame| {
+            b.iter(|| Package::decode(frame).unwrap())
+        });
+        group.bench_with_input(BenchmarkId::new("BytesPackage", name), &bytes, |b, bytes| {
+            b.iter(|| BytesPackage::decode(bytes.clone()).unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, decode);
+criterion_main!(benches);
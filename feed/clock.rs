@@ -0,0 +1,88 @@
+use std::{fmt::Debug, sync::{Arc, OnceLock, atomic::{AtomicU64, Ordering}}, time::{Instant, SystemTime, UNIX_EPOCH}};
+
+/// Source of time for streams and storage, replaceable in tests.
+pub trait Clock: Send + Sync + Debug {
+    /// Wall clock time in milliseconds since the unix epoch.
+    fn now(&self) -> u64;
+
+    /// Monotonic time in microseconds, not affected by wall clock jumps.
+    fn now_mono(&self) -> u64;
+}
+
+pub type ClockRef = Arc<dyn Clock>;
+
+pub fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis().try_into().unwrap()
+}
+
+/// Microseconds since the first call in this process.
+pub fn now_mono() -> u64 {
+    static EPOCH: OnceLock<Instant> = OnceLock::new();
+    EPOCH.get_or_init(Instant::now).elapsed().as_micros().try_into().unwrap()
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> u64 {
+        now()
+    }
+
+    fn now_mono(&self) -> u64 {
+        now_mono()
+    }
+}
+
+/// Clock that only moves when told to, starting at the given wall clock time and monotonic time 0.
+#[derive(Debug, Default)]
+pub struct FakeClock {
+    now: AtomicU64,
+    now_mono: AtomicU64,
+}
+
+impl Clock for FakeClock {
+    fn now(&self) -> u64 {
+        self.now.load(Ordering::SeqCst)
+    }
+
+    fn now_mono(&self) -> u64 {
+        self.now_mono.load(Ordering::SeqCst)
+    }
+}
+
+impl FakeClock {
+    pub fn new(now: u64) -> Arc<FakeClock> {
+        Arc::new(FakeClock { now: AtomicU64::new(now), now_mono: AtomicU64::new(0) })
+    }
+
+    /// Advances both times by `ms` milliseconds.
+    pub fn advance(&self, ms: u64) {
+        self.now.fetch_add(ms, Ordering::SeqCst);
+        self.now_mono.fetch_add(ms * 1000, Ordering::SeqCst);
+    }
+
+    /// Sets the wall clock time only, like a jump of the system clock.
+    pub fn set(&self, now: u64) {
+        self.now.store(now, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fake_clock() {
+        let clock = FakeClock::new(1000);
+        assert_eq!((clock.now(), clock.now_mono()), (1000, 0));
+        clock.advance(5);
+        assert_eq!((clock.now(), clock.now_mono()), (1005, 5000));
+        clock.set(10);
+        assert_eq!((clock.now(), clock.now_mono()), (10, 5000));
+
+        let clock: ClockRef = clock;
+        assert_eq!(clock.now(), 10);
+        assert!(SystemClock.now_mono() <= SystemClock.now_mono());
+    }
+}
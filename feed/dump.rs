@@ -0,0 +1,110 @@
+use std::io::BufRead;
+use serde::{Serialize, Deserialize};
+use serde_json::Result as JsonResult;
+use crate::{package::{Package, JsonPackage}, schema::Event};
+
+/// One line of the jsonl export written by `livekit feed-dump`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Record {
+    pub roomid: u32,
+    pub time: u64,
+    #[serde(flatten)]
+    pub inner: RecordInner,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum RecordInner {
+    Package(JsonPackage),
+    /// Row that is kept in the export although it could not be read.
+    Error(RecordError),
+}
+
+/// Same shape as the variants of `Event` with the same names.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", content = "data")]
+pub enum RecordError {
+    /// Checksum mismatch or `Package::decode` failure, `raw` is the hex of the stored value.
+    CodecError { raw: String, error: String },
+    /// Decoded package with invalid json.
+    ParseError { raw: String, error: String },
+}
+
+impl Record {
+    pub fn codec_error(roomid: u32, time: u64, raw: &[u8], error: String) -> Record {
+        Record { roomid, time, inner: RecordInner::Error(RecordError::CodecError { raw: hex::encode(raw), error }) }
+    }
+
+    pub fn parse_error(roomid: u32, time: u64, raw: &[u8], error: String) -> Record {
+        Record { roomid, time, inner: RecordInner::Error(RecordError::ParseError { raw: hex::encode(raw), error }) }
+    }
+
+    /// Fails for error records, which have no package.
+    pub fn to_package(&self) -> JsonResult<Package> {
+        match &self.inner {
+            RecordInner::Package(package) => package.to_package(),
+            RecordInner::Error(_) => Err(serde::de::Error::custom("error record")),
+        }
+    }
+
+    pub fn to_events(&self) -> JsonResult<Vec<Event>> {
+        Ok(match &self.inner {
+            RecordInner::Package(package) => Event::from_decoded(package.to_package()?),
+            RecordInner::Error(RecordError::CodecError { raw, error }) => vec![Event::CodecError { raw: raw.clone(), error: error.clone() }],
+            RecordInner::Error(RecordError::ParseError { raw, error }) => vec![Event::ParseError { raw: raw.clone(), error: error.clone() }],
+        })
+    }
+}
+
+pub fn read<R: BufRead>(reader: R) -> impl Iterator<Item = JsonResult<Record>> {
+    serde_json::Deserializer::from_reader(reader).into_iter()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::package::PACKAGE_PAYLOAD as DANMAKU;
+    use super::*;
+
+    #[test]
+    fn jsonl_roundtrip() {
+        let packages = vec![
+            Package::Multi(vec![Package::Json(DANMAKU.to_owned()), Package::Json(DANMAKU.to_owned())]),
+            Package::HeartbeatResponse(1),
+            Package::InitResponse(r#"{"code":0}"#.to_owned()),
+        ];
+
+        let mut jsonl = Vec::new();
+        for (time, package) in packages.iter().enumerate() {
+            let record = Record { roomid: 10308958, time: time as u64, inner: RecordInner::Package(package.to_json().unwrap()) };
+            serde_json::to_writer(&mut jsonl, &record).unwrap();
+            jsonl.push(b'\n');
+        }
+
+        let records = read(jsonl.as_slice()).collect::<JsonResult<Vec<_>>>().unwrap();
+        assert_eq!(records.len(), packages.len());
+        for (time, (record, package)) in records.iter().zip(packages).enumerate() {
+            assert_eq!(record.roomid, 10308958);
+            assert_eq!(record.time, time as u64);
+            // compared as json values, key order of objects is not kept
+            assert_eq!(record.to_package().unwrap().to_json().unwrap(), package.to_json().unwrap());
+        }
+
+        let record = Record::codec_error(10308958, 3, &[0; 4], "TooShort".to_owned());
+        let line = serde_json::to_string(&record).unwrap();
+        assert_eq!(line, r#"{"roomid":10308958,"time":3,"type":"CodecError","data":{"raw":"00000000","error":"TooShort"}}"#);
+        let record: Record = serde_json::from_str(&line).unwrap();
+        assert!(record.to_package().is_err());
+        assert!(matches!(&record.to_events().unwrap()[..], [Event::CodecError { raw, .. }] if raw == "00000000"));
+
+        let events = records[0].to_events().unwrap();
+        assert_eq!(events.len(), 2);
+        assert!(events.iter().all(|event| matches!(event, Event::Danmaku(_))));
+        assert!(matches!(records[1].to_events().unwrap()[..], [Event::Popularity(1)]));
+        assert!(matches!(records[2].to_events().unwrap()[..], [Event::InitResponse(0)]));
+
+        for package in [Package::InitRequest(r#"{"roomid":10308958}"#.to_owned()), Package::HeartbeatRequest] {
+            let record = Record { roomid: 10308958, time: 0, inner: RecordInner::Package(package.to_json().unwrap()) };
+            assert!(matches!(record.to_events().unwrap()[..], [Event::ParseError { .. }]));
+        }
+    }
+}
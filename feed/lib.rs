@@ -1,3 +1,5 @@
 pub mod package;
 pub mod schema;
+pub mod clock;
 pub mod stream;
+pub mod dump;
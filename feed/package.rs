@@ -1,3 +1,4 @@
+use bytes::{Bytes, BytesMut};
 use foundations::{byterepr::*, byterepr_struct, error_enum};
 
 byterepr_struct! {
@@ -16,40 +17,232 @@ impl Head {
     pub const SIZE_32: u32 = Self::SIZE as u32;
     
     pub fn new(msg_type: u32, payload_length: u32) -> Self {
+        Self::with_proto_ver(1, msg_type, payload_length)
+    }
+
+    pub fn with_proto_ver(proto_ver: u16, msg_type: u32, payload_length: u32) -> Self {
         Self {
             length: Head::SIZE_32 + payload_length,
             head_length: Head::SIZE_16,
-            proto_ver: 1,
+            proto_ver,
             msg_type,
             seq: 1,
         }
     }
+
+    /// Whether `proto_ver` and `msg_type` are a combination sent by real servers or clients.
+    pub fn is_known_type(&self) -> bool {
+        matches!((self.proto_ver, self.msg_type), (0 | 2 | 3, 5) | (1, 2 | 3 | 7 | 8))
+    }
 }
 
-#[derive(PartialEq, Eq, Debug)]
-pub enum Package {
-    InitRequest(String),
-    InitResponse(String),
+/// Compression used when encoding `Package::Multi`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Compression {
+    /// Sub-packages are concatenated without an outer head, as servers do when compression is off.
+    /// Decode the result with `Package::unpack` rather than `Package::decode`. Nested `Multi`s
+    /// still need a head, so they are compressed with the default to keep their structure.
+    None,
+    Zlib,
+    #[default]
+    Brotli,
+}
+
+/// A decoded package, generic over the type holding its text payloads.
+/// Use `Package` for owned `String`s, or `BytesPackage` to slice the source `Bytes` without copying.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum GenericPackage<S> {
+    InitRequest(S),
+    InitResponse(S),
     HeartbeatRequest,
     HeartbeatResponse(u32),
-    Json(String),
-    Multi(Vec<Package>),
+    Json(S),
+    Multi(Vec<GenericPackage<S>>),
 }
 
-impl Package {
-    pub fn decode(raw: &[u8]) -> PackageCodecResult<Package> {
-        let (head, payload) = raw.split_at(Head::SIZE);
-        let head = Head::from_bytes(head.try_into().unwrap());
-        Package::decode_payload(head, payload)
+pub type Package = GenericPackage<String>;
+pub type BytesPackage = GenericPackage<StrBytes>;
+
+// region: StrBytes
+
+/// `Bytes` known to be valid UTF-8.
+// The field stays private and every constructor validates or starts from a `str`,
+// which `as_str` relies on.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct StrBytes(Bytes);
+
+impl StrBytes {
+    pub fn as_str(&self) -> &str {
+        debug_assert!(std::str::from_utf8(&self.0).is_ok());
+        // SAFETY: only constructed from validated UTF-8, see above
+        unsafe { std::str::from_utf8_unchecked(&self.0) }
     }
 
-    pub fn decode_payload(head: Head, payload: &[u8]) -> PackageCodecResult<Package> {
+    pub fn into_bytes(self) -> Bytes {
+        self.0
+    }
+}
+
+impl TryFrom<Bytes> for StrBytes {
+    type Error = std::str::Utf8Error;
+
+    fn try_from(bytes: Bytes) -> Result<Self, Self::Error> {
+        std::str::from_utf8(&bytes)?;
+        Ok(StrBytes(bytes))
+    }
+}
+
+impl From<String> for StrBytes {
+    fn from(string: String) -> Self {
+        StrBytes(string.into())
+    }
+}
+
+impl From<&'static str> for StrBytes {
+    fn from(string: &'static str) -> Self {
+        StrBytes(Bytes::from_static(string.as_bytes()))
+    }
+}
+
+impl std::ops::Deref for StrBytes {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl AsRef<str> for StrBytes {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl std::fmt::Debug for StrBytes {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl std::fmt::Display for StrBytes {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Display::fmt(self.as_str(), f)
+    }
+}
+
+// endregion
+
+// region: decode
+
+pub const MAX_NESTING_DEPTH: usize = 8;
+pub const MAX_DECOMPRESSED_SIZE: usize = 16 * 1024 * 1024;
+pub const MAX_SUBPACKAGE_COUNT: usize = 16 * 1024;
+
+/// Limits applied while decoding, to keep hostile or broken frames from exhausting memory.
+/// Sizes and counts are totals over the whole frame, including nested packs.
+#[derive(Clone, Debug)]
+pub struct DecodeConfig {
+    /// Maximum nesting level of `Package::Multi`. Real servers never nest more than once.
+    pub max_nesting_depth: usize,
+    /// Maximum number of bytes produced by decompression.
+    pub max_decompressed_size: usize,
+    /// Maximum number of sub-packages unpacked.
+    pub max_subpackage_count: usize,
+}
+
+impl Default for DecodeConfig {
+    fn default() -> Self {
+        DecodeConfig {
+            max_nesting_depth: MAX_NESTING_DEPTH,
+            max_decompressed_size: MAX_DECOMPRESSED_SIZE,
+            max_subpackage_count: MAX_SUBPACKAGE_COUNT,
+        }
+    }
+}
+
+/// Text payload types of `GenericPackage`.
+pub trait PayloadStr: AsRef<str> + Sized {
+    fn from_slice(payload: &[u8]) -> PackageCodecResult<Self>;
+    fn from_bytes(payload: Bytes) -> PackageCodecResult<Self>;
+}
+
+impl PayloadStr for String {
+    fn from_slice(payload: &[u8]) -> PackageCodecResult<Self> {
+        Ok(String::from_utf8(payload.to_owned())?)
+    }
+
+    fn from_bytes(payload: Bytes) -> PackageCodecResult<Self> {
+        String::from_slice(&payload)
+    }
+}
+
+impl PayloadStr for StrBytes {
+    fn from_slice(payload: &[u8]) -> PackageCodecResult<Self> {
+        StrBytes::from_bytes(Bytes::copy_from_slice(payload))
+    }
+
+    fn from_bytes(payload: Bytes) -> PackageCodecResult<Self> {
+        Ok(StrBytes::try_from(payload)?)
+    }
+}
+
+/// Buffers a package can be decoded from. Slicing a `Bytes` shares it instead of copying.
+trait Source: AsRef<[u8]> + Sized {
+    fn slice(&self, range: std::ops::Range<usize>) -> Self;
+    fn into_str<S: PayloadStr>(self) -> PackageCodecResult<S>;
+}
+
+impl Source for &[u8] {
+    fn slice(&self, range: std::ops::Range<usize>) -> Self {
+        &self[range]
+    }
+
+    fn into_str<S: PayloadStr>(self) -> PackageCodecResult<S> {
+        S::from_slice(self)
+    }
+}
+
+impl Source for Bytes {
+    fn slice(&self, range: std::ops::Range<usize>) -> Self {
+        Bytes::slice(self, range)
+    }
+
+    fn into_str<S: PayloadStr>(self) -> PackageCodecResult<S> {
+        S::from_bytes(self)
+    }
+}
+
+struct Decoding<'a> {
+    config: &'a DecodeConfig,
+    decompressed_size: usize,
+    subpackage_count: usize,
+}
+
+impl<'a> Decoding<'a> {
+    fn new(config: &'a DecodeConfig) -> Self {
+        Decoding { config, decompressed_size: 0, subpackage_count: 0 }
+    }
+
+    fn decode<S: PayloadStr, B: Source>(&mut self, raw: B, depth: usize) -> PackageCodecResult<GenericPackage<S>> {
+        let length = raw.as_ref().len();
+        if length < Head::SIZE {
+            return Err(PackageCodecError::TooShort { need: Head::SIZE, got: length });
+        }
+        let head = Head::from_bytes(raw.as_ref()[..Head::SIZE].try_into()?);
+        self.decode_payload(head, raw.slice(Head::SIZE..length), depth)
+    }
+
+    fn decode_payload<S: PayloadStr, B: Source>(&mut self, head: Head, payload: B, depth: usize) -> PackageCodecResult<GenericPackage<S>> {
         if head.head_length != Head::SIZE_16 {
             return Err(PackageCodecError::UnknownHeadLength(head.head_length));
         }
 
-        let payload_length_head = head.length - Head::SIZE_32;
-        let payload_length_acc: u32 = payload.len().try_into()?;
+        if depth > self.config.max_nesting_depth {
+            return Err(PackageCodecError::NestingTooDeep(depth));
+        }
+
+        let payload_length_head = head.length.checked_sub(Head::SIZE_32).ok_or(PackageCodecError::LengthUnderflow(head.length))?;
+        let payload_length_acc: u32 = payload.as_ref().len().try_into()?;
         if payload_length_head != payload_length_acc {
             return Err(PackageCodecError::IncorrectPayloadLength { head: payload_length_head, acc: payload_length_acc });
         }
@@ -64,75 +257,227 @@ impl Package {
 
         macro_rules! string {
             () => {
-                String::from_utf8(payload.to_owned())?
+                payload.into_str::<S>()?
             };
         }
 
         macro_rules! u32 {
             () => {
-                u32::from_be_bytes(payload.try_into()?)
+                u32::from_be_bytes(payload.as_ref().try_into()?)
             };
         }
 
-        macro_rules! br {
-            () => {{
+        macro_rules! decompress {
+            ($decoder:expr) => {{
+                use std::io::Read;
+                let limit = self.config.max_decompressed_size;
+                let remaining = limit - self.decompressed_size;
                 let mut decoded = Vec::new();
-                brotli_decompressor::BrotliDecompress(&mut std::io::Cursor::new(payload), &mut std::io::Cursor::new(&mut decoded))?;
+                $decoder.take((remaining as u64).saturating_add(1)).read_to_end(&mut decoded)?;
+                if decoded.len() > remaining {
+                    return Err(PackageCodecError::DecompressedTooLarge { limit });
+                }
+                self.decompressed_size += decoded.len();
                 decoded
             }};
         }
 
+        macro_rules! inflate {
+            () => {
+                decompress!(flate2::read::ZlibDecoder::new(payload.as_ref()))
+            };
+        }
+
+        macro_rules! br {
+            () => {
+                decompress!(brotli_decompressor::Decompressor::new(payload.as_ref(), 4096))
+            };
+        }
+
         // endregion
 
         Ok(match head.proto_ver {
-            0 => Package::Json(string!()),
-            3 => Package::unpack(br!())?,
+            0 => GenericPackage::Json(string!()),
+            2 => {
+                let pack = Bytes::from(inflate!());
+                self.unpack(pack, depth + 1)?
+            },
+            3 => {
+                let pack = Bytes::from(br!());
+                self.unpack(pack, depth + 1)?
+            },
             1 => match head.msg_type {
-                3 => Package::HeartbeatResponse(u32!()),
-                8 => Package::InitResponse(string!()),
-                2 => Package::HeartbeatRequest,
-                7 => Package::InitRequest(string!()),
+                3 => GenericPackage::HeartbeatResponse(u32!()),
+                8 => GenericPackage::InitResponse(string!()),
+                2 => GenericPackage::HeartbeatRequest,
+                7 => GenericPackage::InitRequest(string!()),
                 _ => unknown_type!(),
             },
-            // 2 => Package::unpack(inflate!())?,
             _ => unknown_type!(),
         })
     }
 
-    pub fn encode(self) -> PackageCodecResult<Vec<u8>> {
-        Ok(match self {
-            Package::HeartbeatRequest => Head::new(2, 0).to_bytes().to_vec(),
-            Package::InitRequest(payload) => {
-                [
-                    &Head::new(7, payload.len().try_into()?).to_bytes(),
-                    payload.as_bytes(),
-                ].concat()
-            },
-            _ => return Err(PackageCodecError::NotEncodable),
-        })
-    }
-
-    fn unpack<B: AsRef<[u8]>>(pack: B) -> PackageCodecResult<Package> {
-        let pack = pack.as_ref();
-        let total_length = pack.len();
+    fn unpack<S: PayloadStr, B: Source>(&mut self, pack: B, depth: usize) -> PackageCodecResult<GenericPackage<S>> {
+        let total_length = pack.as_ref().len();
         let mut unpacked = Vec::new();
         let mut offset = 0;
         while offset < total_length {
-            let length_buf = pack[offset..offset + 4].try_into()?;
-            let length: usize = u32::from_be_bytes(length_buf).try_into()?;
-            unpacked.push(Package::decode(&pack[offset..offset + length])?);
+            let rest = &pack.as_ref()[offset..];
+            if rest.len() < 4 {
+                return Err(PackageCodecError::TooShort { need: 4, got: rest.len() });
+            }
+            let length: usize = u32::from_be_bytes(rest[..4].try_into()?).try_into()?;
+            if length == 0 {
+                return Err(PackageCodecError::ZeroLengthSubpackage { offset });
+            }
+            if length > rest.len() {
+                return Err(PackageCodecError::LengthOverflow { offset, length, total_length });
+            }
+            self.subpackage_count += 1;
+            if self.subpackage_count > self.config.max_subpackage_count {
+                return Err(PackageCodecError::TooManySubpackages { limit: self.config.max_subpackage_count });
+            }
+            unpacked.push(self.decode(pack.slice(offset..offset + length), depth)?);
             offset += length;
         }
         if offset != total_length {
             return Err(PackageCodecError::UnpackLeak { offset, total_length });
         }
-        Ok(Package::Multi(unpacked))
+        Ok(GenericPackage::Multi(unpacked))
+    }
+}
+
+impl Package {
+    pub fn decode(raw: &[u8]) -> PackageCodecResult<Package> {
+        Package::decode_with(raw, &DecodeConfig::default())
+    }
+
+    pub fn decode_with(raw: &[u8], config: &DecodeConfig) -> PackageCodecResult<Package> {
+        Decoding::new(config).decode(raw, 0)
+    }
+
+    pub fn decode_payload(head: Head, payload: &[u8]) -> PackageCodecResult<Package> {
+        Package::decode_payload_with(head, payload, &DecodeConfig::default())
+    }
+
+    pub fn decode_payload_with(head: Head, payload: &[u8], config: &DecodeConfig) -> PackageCodecResult<Package> {
+        Decoding::new(config).decode_payload(head, payload, 0)
+    }
+
+    pub fn unpack<B: AsRef<[u8]>>(pack: B) -> PackageCodecResult<Package> {
+        Package::unpack_with(pack, &DecodeConfig::default())
+    }
+
+    pub fn unpack_with<B: AsRef<[u8]>>(pack: B, config: &DecodeConfig) -> PackageCodecResult<Package> {
+        Decoding::new(config).unpack(pack.as_ref(), 0)
+    }
+}
+
+impl BytesPackage {
+    pub fn decode(raw: Bytes) -> PackageCodecResult<BytesPackage> {
+        BytesPackage::decode_with(raw, &DecodeConfig::default())
+    }
+
+    pub fn decode_with(raw: Bytes, config: &DecodeConfig) -> PackageCodecResult<BytesPackage> {
+        Decoding::new(config).decode(raw, 0)
+    }
+
+    pub fn decode_payload(head: Head, payload: Bytes) -> PackageCodecResult<BytesPackage> {
+        BytesPackage::decode_payload_with(head, payload, &DecodeConfig::default())
+    }
+
+    pub fn decode_payload_with(head: Head, payload: Bytes, config: &DecodeConfig) -> PackageCodecResult<BytesPackage> {
+        Decoding::new(config).decode_payload(head, payload, 0)
     }
 
-    pub fn flatten(self) -> Vec<Package> {
+    pub fn unpack(pack: Bytes) -> PackageCodecResult<BytesPackage> {
+        BytesPackage::unpack_with(pack, &DecodeConfig::default())
+    }
+
+    pub fn unpack_with(pack: Bytes, config: &DecodeConfig) -> PackageCodecResult<BytesPackage> {
+        Decoding::new(config).unpack(pack, 0)
+    }
+}
+
+// endregion
+
+impl<S: AsRef<str>> GenericPackage<S> {
+    pub fn encode(self) -> PackageCodecResult<Vec<u8>> {
+        self.encode_with(Compression::default())
+    }
+
+    pub fn encode_with(self, compression: Compression) -> PackageCodecResult<Vec<u8>> {
+        self.encode_inner(compression, 1)
+    }
+
+    /// Encodes with `seq` written into the head, for clients numbering the packages they send.
+    pub fn encode_seq(self, seq: u32) -> PackageCodecResult<Vec<u8>> {
+        self.encode_inner(Compression::default(), seq)
+    }
+
+    fn encode_inner(self, compression: Compression, seq: u32) -> PackageCodecResult<Vec<u8>> {
+        // region: macros
+
+        macro_rules! frame {
+            ($proto_ver:expr, $msg_type:expr, $payload:expr) => {{
+                let payload: &[u8] = $payload;
+                let head = Head { seq, ..Head::with_proto_ver($proto_ver, $msg_type, payload.len().try_into()?) };
+                [
+                    head.to_bytes().as_slice(),
+                    payload,
+                ].concat()
+            }};
+        }
+
+        macro_rules! deflate {
+            ($packed:expr) => {{
+                use std::io::Write;
+                let mut encoder = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(&$packed)?;
+                encoder.finish()?
+            }};
+        }
+
+        macro_rules! br {
+            ($packed:expr) => {{
+                let mut encoded = Vec::new();
+                brotli::BrotliCompress(&mut std::io::Cursor::new($packed), &mut encoded, &Default::default())?;
+                encoded
+            }};
+        }
+
+        // endregion
+
+        Ok(match self {
+            GenericPackage::Json(payload) => frame!(0, 5, payload.as_ref().as_bytes()),
+            GenericPackage::HeartbeatResponse(payload) => frame!(1, 3, &payload.to_be_bytes()),
+            GenericPackage::InitResponse(payload) => frame!(1, 8, payload.as_ref().as_bytes()),
+            GenericPackage::HeartbeatRequest => frame!(1, 2, &[]),
+            GenericPackage::InitRequest(payload) => frame!(1, 7, payload.as_ref().as_bytes()),
+            GenericPackage::Multi(packages) => {
+                let nested = match compression {
+                    Compression::None => Compression::default(),
+                    compression => compression,
+                };
+                let mut packed = Vec::new();
+                for package in packages {
+                    packed.extend(package.encode_inner(nested, seq)?);
+                }
+                match compression {
+                    Compression::None => packed,
+                    Compression::Zlib => frame!(2, 5, &deflate!(packed)),
+                    Compression::Brotli => frame!(3, 5, &br!(packed)),
+                }
+            },
+        })
+    }
+}
+
+impl<S> GenericPackage<S> {
+    pub fn flatten(self) -> Vec<GenericPackage<S>> {
         let mut flattened = Vec::new();
-        fn inner(package: Package, flattened: &mut Vec<Package>) {
-            if let Package::Multi(packages) = package {
+        fn inner<S>(package: GenericPackage<S>, flattened: &mut Vec<GenericPackage<S>>) {
+            if let GenericPackage::Multi(packages) = package {
                 for sub_package in packages {
                     inner(sub_package, flattened)
                 }
@@ -145,12 +490,81 @@ impl Package {
     }
 }
 
+// region: frame
+
+pub const MAX_FRAME_LENGTH: usize = 16 * 1024 * 1024;
+
+/// Splits a byte stream into complete frames using the length field of their heads.
+/// Callers append arbitrary chunks to a `BytesMut` and call `decode_frame` until it returns `None`.
+/// Frames are split off the buffer without copying.
+#[derive(Clone, Debug)]
+pub struct FrameDecoder {
+    max_length: usize,
+}
+
+impl Default for FrameDecoder {
+    fn default() -> Self {
+        FrameDecoder::new(MAX_FRAME_LENGTH)
+    }
+}
+
+impl FrameDecoder {
+    pub fn new(max_length: usize) -> Self {
+        FrameDecoder { max_length }
+    }
+
+    pub fn decode_frame(&mut self, buf: &mut BytesMut) -> PackageCodecResult<Option<Bytes>> {
+        if buf.len() < 4 {
+            return Ok(None);
+        }
+        let length = u32::from_be_bytes(buf[..4].try_into()?);
+        if length < Head::SIZE_32 {
+            return Err(PackageCodecError::LengthUnderflow(length));
+        }
+        let length: usize = length.try_into()?;
+        if length > self.max_length {
+            return Err(PackageCodecError::FrameTooLarge { length, limit: self.max_length });
+        }
+        if buf.len() < length {
+            buf.reserve(length - buf.len());
+            return Ok(None);
+        }
+        Ok(Some(buf.split_to(length).freeze()))
+    }
+
+    pub fn decode_frame_eof(&mut self, buf: &mut BytesMut) -> PackageCodecResult<Option<Bytes>> {
+        match self.decode_frame(buf)? {
+            Some(frame) => Ok(Some(frame)),
+            None if buf.is_empty() => Ok(None),
+            None => {
+                let need = if buf.len() < 4 { 4 } else { u32::from_be_bytes(buf[..4].try_into()?).try_into()? };
+                Err(PackageCodecError::TooShort { need, got: buf.len() })
+            },
+        }
+    }
+}
+
+impl tokio_util::codec::Decoder for FrameDecoder {
+    type Item = Bytes;
+    type Error = PackageCodecError;
+
+    fn decode(&mut self, src: &mut BytesMut) -> PackageCodecResult<Option<Bytes>> {
+        self.decode_frame(src)
+    }
+
+    fn decode_eof(&mut self, src: &mut BytesMut) -> PackageCodecResult<Option<Bytes>> {
+        self.decode_frame_eof(src)
+    }
+}
+
+// endregion
+
 // region: to_json
 
-use serde::Serialize;
+use serde::{Serialize, Deserialize};
 use serde_json::{Value as JsonValue, Result as JsonResult};
 
-#[derive(Debug, Serialize)]
+#[derive(PartialEq, Debug, Serialize, Deserialize)]
 #[serde(tag = "type", content = "payload")]
 pub enum JsonPackage {
     InitRequest(JsonValue),
@@ -161,15 +575,29 @@ pub enum JsonPackage {
     Multi(Vec<JsonPackage>),
 }
 
-impl Package {
+impl<S: AsRef<str>> GenericPackage<S> {
     pub fn to_json(&self) -> JsonResult<JsonPackage> {
         Ok(match self {
-            Package::InitRequest(s) => JsonPackage::InitRequest(serde_json::from_str(s)?),
-            Package::InitResponse(s) => JsonPackage::InitResponse(serde_json::from_str(s)?),
-            Package::HeartbeatRequest => JsonPackage::HeartbeatRequest,
-            Package::HeartbeatResponse(n) => JsonPackage::HeartbeatResponse(*n),
-            Package::Json(s) => JsonPackage::Json(serde_json::from_str(s)?),
-            Package::Multi(v) => JsonPackage::Multi(v.iter().map(|p| p.to_json()).collect::<JsonResult<Vec<_>>>()?)
+            GenericPackage::InitRequest(s) => JsonPackage::InitRequest(serde_json::from_str(s.as_ref())?),
+            GenericPackage::InitResponse(s) => JsonPackage::InitResponse(serde_json::from_str(s.as_ref())?),
+            GenericPackage::HeartbeatRequest => JsonPackage::HeartbeatRequest,
+            GenericPackage::HeartbeatResponse(n) => JsonPackage::HeartbeatResponse(*n),
+            GenericPackage::Json(s) => JsonPackage::Json(serde_json::from_str(s.as_ref())?),
+            GenericPackage::Multi(v) => JsonPackage::Multi(v.iter().map(|p| p.to_json()).collect::<JsonResult<Vec<_>>>()?)
+        })
+    }
+}
+
+impl JsonPackage {
+    /// JSON payloads are re-serialized, so whitespace and escapes in the original payload are normalized.
+    pub fn to_package(&self) -> JsonResult<Package> {
+        Ok(match self {
+            JsonPackage::InitRequest(v) => Package::InitRequest(serde_json::to_string(v)?),
+            JsonPackage::InitResponse(v) => Package::InitResponse(serde_json::to_string(v)?),
+            JsonPackage::HeartbeatRequest => Package::HeartbeatRequest,
+            JsonPackage::HeartbeatResponse(n) => Package::HeartbeatResponse(*n),
+            JsonPackage::Json(v) => Package::Json(serde_json::to_string(v)?),
+            JsonPackage::Multi(v) => Package::Multi(v.iter().map(|p| p.to_package()).collect::<JsonResult<Vec<_>>>()?)
         })
     }
 }
@@ -181,13 +609,21 @@ error_enum! {
     pub enum PackageCodecError {
         UnknownHeadLength(u16),
         IncorrectPayloadLength { head: u32, acc: u32 },
+        TooShort { need: usize, got: usize },
+        LengthUnderflow(u32),
+        LengthOverflow { offset: usize, length: usize, total_length: usize },
+        ZeroLengthSubpackage { offset: usize },
+        NestingTooDeep(usize),
+        DecompressedTooLarge { limit: usize },
+        TooManySubpackages { limit: usize },
+        FrameTooLarge { length: usize, limit: usize },
         UnpackLeak { offset: usize, total_length: usize },
         UnknownPayloadType(Head),
-        NotEncodable,
     }
     convert {
         IoError          => std::io::Error,
         StringCodecError => std::string::FromUtf8Error,
+        StrCodecError    => std::str::Utf8Error,
         BytesSilceError  => std::array::TryFromSliceError,
         SizeConvertError => std::num::TryFromIntError,
     }
@@ -203,6 +639,10 @@ impl std::error::Error for PackageCodecError {}
 
 pub type PackageCodecResult<T> = Result<T, PackageCodecError>;
 
+// payload of the `PACKAGE_RAW` test frame, also used by the benches
+#[cfg(test)]
+pub(crate) const PACKAGE_PAYLOAD: &str = include_str!("fixtures/danmaku.json");
+
 #[cfg(test)]
 mod tests {
     use hex_literal::hex;
@@ -238,7 +678,10 @@ mod tests {
     }
 
     const PACKAGE_RAW: [u8; 289] = hex!("000001210010000300000005000000001b7c01002c0e32b9173be1482c4d132ebcf86bd4ac5ab67f8247585ab7e899d9684941a296550487e250572f9cbde7d3855c45cd6486cd6c4213f89e2c3a7de5f4954153694f0f380e4c5a81b52c6901061aca897fb8fdf35f6f58f6900f39a47c9ed5dd6e7fd85dec14ce77532b3e7e3e116e787dfbda3d60de63348668f4ccdcacd4de6825acd4d24c45a5b250ab534449aed4d237c815305c0ff0497069e5dfa4787eb9c46f70e41a353c9213ff5fb6c02de1580d46513449a211981cd6886df9d86f3305f0abb3734e701734600ab59e1b2dd4ac752740a14b1e8a46ab2d794f6ad3b0a058928a4722deffa4f8ca92049a06406052142f61b062f9455bd9203e1604bff1abbb729d30db2520c96e73");
-    const PACKAGE_PAYLOAD: &str = "{\"cmd\":\"DANMU_MSG\",\"info\":[[0,1,25,5816798,1631676810606,1631676772,0,\"6420484f\",0,0,0,\"\",0,\"{}\",\"{}\"],\"Hello, LiveKit!!!\",[573732342,\"进栈检票\",1,0,0,10000,1,\"\"],[18,\"滑稽果\",\"老弟一号\",10308958,13081892,\"\",0,13081892,13081892,13081892,0,1,178429408],[13,0,6406234,\"\\u003e50000\",0],[\"\",\"\"],0,0,null,{\"ts\":1631676810,\"ct\":\"2D2BF6C4\"},0,0,null,null,0,91]}";
+
+    // Not captured: the frame of `PACKAGE_RAW` recompressed with zlib (level 6) under a proto_ver 2 head,
+    // as no real proto_ver 2 frame is at hand. Replace it once one is taken from an archive.
+    const PACKAGE_RAW_ZLIB: [u8; 302] = hex!("0000012e001000020000000500000000789c65503d4bc440108d85bd3fc14c3dc5ec477637e9d443053d1bb18a87c5998340bc2bccd98440ececc45a514184fb070a2afe192f773fc3d913bdc25978fbdeeecedbc704c14a1dac05be563d54d03f3b85043a1b07dda393eee10e20e4c3c1089234251428238c9c303676288c62629c2043e657592b91108c96a49d1e000bbfc013a86a58400f61372b8a11aeefe717d95e5e8661089846565925959608f3cfdbf6f1aa7d6a66cf13e06fbd87202ee6c0fda97008eddbcd6cf2d1dedfb1ebbcb99cbe3f7cbd36d3eb176e20452e8e3823efc2c5f227c09ffa4fbcb1b04ecb5893f3fe8a8f8c26c379108ec7442a8b7c00f6e16bb6f3317cace1b828b082f21c92e54410fa254f5176e4e6b6d9d2502f9f2e803016bdfa1b7c16640a");
 
     macro_rules! pkg_json {
         ($payload:expr) => {
@@ -254,6 +697,58 @@ mod tests {
         )
     }
 
+    #[test]
+    fn test_package_decode_zlib() {
+        assert_eq!(
+            Package::decode(&PACKAGE_RAW_ZLIB).unwrap(),
+            Package::Multi(vec![pkg_json!(PACKAGE_PAYLOAD)])
+        );
+
+        let mut corrupted = PACKAGE_RAW_ZLIB;
+        corrupted[Head::SIZE + 2..Head::SIZE + 10].fill(0xff);
+        assert!(matches!(Package::decode(&corrupted), Err(PackageCodecError::IoError(_))));
+    }
+
+    #[test]
+    fn encode_roundtrip() {
+        let packages = vec![
+            Package::InitRequest(r#"{"roomid":10308958}"#.to_owned()),
+            Package::InitResponse(r#"{"code":0}"#.to_owned()),
+            Package::HeartbeatRequest,
+            Package::HeartbeatResponse(42),
+            pkg_json!(PACKAGE_PAYLOAD),
+        ];
+        for package in packages.clone() {
+            assert_eq!(Package::decode(&package.clone().encode().unwrap()).unwrap(), package);
+        }
+
+        let multi = Package::Multi(vec![
+            Package::Multi(vec![pkg_json!(PACKAGE_PAYLOAD), Package::HeartbeatResponse(1)]),
+            pkg_json!(PACKAGE_PAYLOAD),
+        ]);
+        for compression in [Compression::Zlib, Compression::Brotli] {
+            assert_eq!(Package::decode(&multi.clone().encode_with(compression).unwrap()).unwrap(), multi);
+        }
+
+        let multi = Package::Multi(packages);
+        assert_eq!(Package::unpack(multi.clone().encode_with(Compression::None).unwrap()).unwrap(), multi);
+    }
+
+    #[test]
+    fn bytes_package_decode() {
+        let raw = Bytes::from(pkg_json!(PACKAGE_PAYLOAD).encode().unwrap());
+        let package = BytesPackage::decode(raw.clone()).unwrap();
+        let BytesPackage::Json(payload) = &package else { unreachable!() };
+        assert_eq!(payload.as_str(), PACKAGE_PAYLOAD);
+        assert_eq!(payload.as_ptr(), raw[Head::SIZE..].as_ptr());
+
+        for raw in [&PACKAGE_RAW[..], &PACKAGE_RAW_ZLIB[..]] {
+            let package = BytesPackage::decode(Bytes::copy_from_slice(raw)).unwrap();
+            assert_eq!(package, BytesPackage::Multi(vec![BytesPackage::Json(PACKAGE_PAYLOAD.into())]));
+            assert_eq!(format!("{:?}", package.to_json().unwrap()), format!("{:?}", Package::decode(raw).unwrap().to_json().unwrap()));
+        }
+    }
+
     #[test]
     fn flat_related() {
         assert_eq!(
@@ -296,4 +791,156 @@ mod tests {
         assert!(matches!(Package::decode(&less), Err(PackageCodecError::BytesSilceError(_))));
         assert!(matches!(Package::decode(&non_align), Err(PackageCodecError::IncorrectPayloadLength { .. })));
     }
+
+    #[test]
+    fn malformed_frames() {
+        let frame = pkg_json!(PACKAGE_PAYLOAD).encode().unwrap();
+
+        assert!(matches!(Package::decode(&[]), Err(PackageCodecError::TooShort { need: 16, got: 0 })));
+        assert!(matches!(Package::decode(&frame[..10]), Err(PackageCodecError::TooShort { need: 16, got: 10 })));
+        assert!(matches!(Package::decode(&frame[..100]), Err(PackageCodecError::IncorrectPayloadLength { .. })));
+
+        let mut underflow = frame.clone();
+        underflow[..4].copy_from_slice(&8u32.to_be_bytes());
+        assert!(matches!(Package::decode(&underflow), Err(PackageCodecError::LengthUnderflow(8))));
+
+        macro_rules! pack {
+            ($($part:expr),*) => {
+                [$($part.as_slice()),*].concat()
+            };
+        }
+
+        let overflow = pack!(frame, 0xffffu32.to_be_bytes());
+        assert!(matches!(Package::unpack(overflow), Err(PackageCodecError::LengthOverflow { length: 0xffff, .. })));
+        let zero = pack!(frame, [0u8; 4]);
+        assert!(matches!(Package::unpack(zero), Err(PackageCodecError::ZeroLengthSubpackage { .. })));
+        let trailing = pack!(frame, [0u8; 3]);
+        assert!(matches!(Package::unpack(trailing), Err(PackageCodecError::TooShort { need: 4, got: 3 })));
+    }
+
+    #[test]
+    fn nesting_depth() {
+        let nest = |depth| (0..depth).fold(Package::HeartbeatResponse(1), |package, _| Package::Multi(vec![package]));
+        let allowed = nest(MAX_NESTING_DEPTH);
+        assert_eq!(Package::decode(&allowed.clone().encode().unwrap()).unwrap(), allowed);
+        let too_deep = nest(MAX_NESTING_DEPTH + 1);
+        assert!(matches!(Package::decode(&too_deep.encode().unwrap()), Err(PackageCodecError::NestingTooDeep(_))));
+    }
+
+    #[test]
+    fn decode_limits() {
+        let bomb = Package::Multi(vec![pkg_json!(" ".repeat(1024 * 1024))]).encode().unwrap();
+        assert!(bomb.len() < 1024);
+        let config = DecodeConfig { max_decompressed_size: 1024 * 1024, ..Default::default() };
+        assert!(matches!(Package::decode_with(&bomb, &config), Err(PackageCodecError::DecompressedTooLarge { .. })));
+        let config = DecodeConfig { max_decompressed_size: 2 * 1024 * 1024, ..Default::default() };
+        assert!(Package::decode_with(&bomb, &config).is_ok());
+        // usize::MAX as unlimited, for both compressions
+        let config = DecodeConfig { max_decompressed_size: usize::MAX, ..Default::default() };
+        assert_eq!(Package::decode_with(&bomb, &config).unwrap(), Package::Multi(vec![pkg_json!(" ".repeat(1024 * 1024))]));
+        let zlib = Package::Multi(vec![Package::HeartbeatResponse(1); 2]).encode_with(Compression::Zlib).unwrap();
+        assert_eq!(Package::decode_with(&zlib, &config).unwrap(), Package::Multi(vec![Package::HeartbeatResponse(1); 2]));
+
+        let many = Package::Multi(vec![Package::HeartbeatResponse(1); 8]).encode_with(Compression::Zlib).unwrap();
+        let config = DecodeConfig { max_subpackage_count: 7, ..Default::default() };
+        assert!(matches!(Package::decode_with(&many, &config), Err(PackageCodecError::TooManySubpackages { limit: 7 })));
+        let config = DecodeConfig { max_subpackage_count: 8, ..Default::default() };
+        assert!(Package::decode_with(&many, &config).is_ok());
+    }
+
+    #[test]
+    fn frame_decoder() {
+        let frames: Vec<Vec<u8>> = vec![
+            PACKAGE_RAW.to_vec(),
+            Package::HeartbeatResponse(1).encode().unwrap(),
+            PACKAGE_RAW_ZLIB.to_vec(),
+        ];
+        let stream = frames.concat();
+
+        for chunk_size in [1, 3, 16, 100, stream.len()] {
+            let mut decoder = FrameDecoder::default();
+            let mut buf = BytesMut::new();
+            let mut decoded = Vec::new();
+            for chunk in stream.chunks(chunk_size) {
+                buf.extend_from_slice(chunk);
+                while let Some(frame) = decoder.decode_frame(&mut buf).unwrap() {
+                    decoded.push(frame.to_vec());
+                }
+            }
+            assert!(decoder.decode_frame_eof(&mut buf).unwrap().is_none());
+            assert_eq!(decoded, frames);
+        }
+
+        let mut decoder = FrameDecoder::new(256);
+        let mut buf = BytesMut::from(&PACKAGE_RAW[..]);
+        assert!(matches!(decoder.decode_frame(&mut buf), Err(PackageCodecError::FrameTooLarge { length: 289, limit: 256 })));
+
+        let mut decoder = FrameDecoder::default();
+        let mut buf = BytesMut::from(&PACKAGE_RAW[..100]);
+        assert!(decoder.decode_frame(&mut buf).unwrap().is_none());
+        assert!(matches!(decoder.decode_frame_eof(&mut buf), Err(PackageCodecError::TooShort { need: 289, got: 100 })));
+    }
+
+    mod prop {
+        use proptest::prelude::*;
+        use super::super::*;
+
+        fn package() -> impl Strategy<Value = Package> {
+            let leaf = prop_oneof![
+                any::<String>().prop_map(Package::InitRequest),
+                any::<String>().prop_map(Package::InitResponse),
+                Just(Package::HeartbeatRequest),
+                any::<u32>().prop_map(Package::HeartbeatResponse),
+                any::<String>().prop_map(Package::Json),
+            ];
+            leaf.prop_recursive(3, 32, 4, |inner| prop::collection::vec(inner, 0..4).prop_map(Package::Multi))
+        }
+
+        fn compression() -> impl Strategy<Value = Compression> {
+            prop_oneof![Just(Compression::Zlib), Just(Compression::Brotli)]
+        }
+
+        proptest! {
+            #[test]
+            fn roundtrip(package in package(), compression in prop_oneof![Just(Compression::None), compression()]) {
+                let encoded = package.clone().encode_with(compression).unwrap();
+                if compression == Compression::None {
+                    // `unpack` always gives a `Multi`, and only the outermost one is left without a head
+                    let package = match package {
+                        package @ Package::Multi(_) => package,
+                        package => Package::Multi(vec![package]),
+                    };
+                    let bytes_package = BytesPackage::unpack(Bytes::from(encoded.clone())).unwrap();
+                    prop_assert_eq!(format!("{:?}", bytes_package), format!("{:?}", package));
+                    prop_assert_eq!(Package::unpack(&encoded).unwrap(), package);
+                } else {
+                    let bytes_package = BytesPackage::decode(Bytes::from(encoded.clone())).unwrap();
+                    prop_assert_eq!(format!("{:?}", bytes_package), format!("{:?}", package));
+                    prop_assert_eq!(Package::decode(&encoded).unwrap(), package);
+                }
+            }
+
+            #[test]
+            fn arbitrary_bytes(raw in prop::collection::vec(any::<u8>(), 0..512)) {
+                let _ = Package::decode(&raw);
+                let _ = Package::unpack(&raw);
+            }
+
+            #[test]
+            fn corrupted_frame(
+                package in package(),
+                compression in compression(),
+                flips in prop::collection::vec((any::<prop::sample::Index>(), any::<u8>()), 1..8),
+                cut in any::<prop::sample::Index>(),
+            ) {
+                let mut encoded = package.encode_with(compression).unwrap();
+                for (index, byte) in flips {
+                    let index = index.index(encoded.len());
+                    encoded[index] ^= byte;
+                }
+                let _ = Package::decode(&encoded);
+                let _ = Package::decode(&encoded[..cut.index(encoded.len())]);
+            }
+        }
+    }
 }
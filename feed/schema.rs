@@ -75,6 +75,11 @@ pub fn string_u32(value: &JsonValue) -> JsonResult<u32> {
     Ok(string.parse::<u32>().unwrap())
 }
 
+pub fn string_u64(value: &JsonValue) -> JsonResult<u64> {
+    let string: String = to(value)?;
+    string.parse::<u64>().map_err(serde::de::Error::custom)
+}
+
 pub fn string_color_to_u32(value: &JsonValue) -> JsonResult<u32> {
     if value.is_string() {
         let string: String = to(value)?;
@@ -96,15 +101,17 @@ pub fn string_color_to_u32(value: &JsonValue) -> JsonResult<u32> {
 
 // region: InitRequest & InitResponse
 
-#[derive(Debug, Serialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct InitRequest {
     pub uid: u64,
     pub roomid: u32,
     pub protover: u8,
-    pub buvid: String,
-    pub platform: &'static str,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub buvid: Option<String>,
+    pub platform: String,
     pub r#type: u8,
-    pub key: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
 }
 
 impl InitRequest {
@@ -113,10 +120,10 @@ impl InitRequest {
             uid,
             roomid,
             protover: 3,
-            buvid: devid3,
-            platform: "web",
+            buvid: Some(devid3),
+            platform: "web".to_owned(),
             r#type: 2,
-            key: token,
+            key: Some(token),
         }
     }
 }
@@ -136,7 +143,7 @@ impl InitResponse {
 
 // region: (common)
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Medal {
     pub on: bool,
     pub level: u8,
@@ -196,7 +203,7 @@ impl Medal {
     }
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct User {
     pub uid: u64,
     pub uname: String,
@@ -206,7 +213,7 @@ pub struct User {
     pub laoye_annual: bool,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Title(String, Option<String>);
 
 impl Title {
@@ -231,7 +238,7 @@ impl Title {
 
 // region: Danmaku
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Danmaku {
     info: DanmakuInfo,
     user: User,
@@ -240,7 +247,7 @@ pub struct Danmaku {
     title: Option<Title>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct DanmakuInfo {
     pub time: i64,
     pub text: String,
@@ -290,7 +297,7 @@ impl Danmaku {
 
 // region: Interact
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Interact {
     kind: InteractKind,
     time: i64, // sec
@@ -299,7 +306,7 @@ pub struct Interact {
     medal: Option<Medal>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub enum InteractKind {
     Enter,
     Follow,
@@ -336,7 +343,7 @@ impl Interact {
 
 // region: Gift
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Gift {
     time: i64, // sec
     uid: u64,
@@ -365,9 +372,40 @@ impl Gift {
 
 // endregion
 
+// region: GiftCombo
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GiftCombo {
+    id: String,
+    uid: u64,
+    uname: String,
+    gift_id: i32,
+    gift_name: String,
+    total_count: u32,
+    batch_count: u32,
+    medal: Option<Medal>,
+}
+
+impl GiftCombo {
+    fn from(raw: &JsonValue) -> JsonResult<Self> {
+        Ok(GiftCombo {
+            id: to(&raw["combo_id"])?,
+            uid: to(&raw["uid"])?,
+            uname: to(&raw["uname"])?,
+            gift_id: to(&raw["gift_id"])?,
+            gift_name: to(&raw["gift_name"])?,
+            total_count: to(&raw["total_num"])?,
+            batch_count: to(&raw["batch_combo_num"])?,
+            medal: Medal::from_common(&raw["medal_info"])?,
+        })
+    }
+}
+
+// endregion
+
 // region: GuardBuy
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct GuardBuy {
     time: i64, // sec
     uid: u64,
@@ -394,23 +432,30 @@ impl GuardBuy {
 
 // region: SuperChat
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct SuperChat {
+    id: u64,
     time: i64, // sec
     text: String,
     price: u32,
     duration: u32,
+    background_color: u32,
+    price_color: u32,
     user: User,
     uface: String,
+    medal: Option<Medal>,
 }
 
 impl SuperChat {
     fn from(raw: &JsonValue, user: &JsonValue) -> JsonResult<Self> {
         Ok(SuperChat {
+            id: to(&raw["id"])?,
             time: to(&raw["ts"])?,
             text: to(&raw["message"])?,
             price: to(&raw["price"])?,
             duration: to(&raw["time"])?,
+            background_color: string_color_to_u32(&raw["background_color"])?,
+            price_color: string_color_to_u32(&raw["background_price_color"])?,
             user: User {
                 uid: to(&raw["uid"])?,
                 uname: to(&user["uname"])?,
@@ -420,6 +465,251 @@ impl SuperChat {
                 laoye_annual: numbool(&user["is_svip"])?,
             },
             uface: to(&user["face"])?,
+            // null rather than an empty medal for users without one
+            medal: if raw["medal_info"].is_null() { None } else { Medal::from_common(&raw["medal_info"])? },
+        })
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SuperChatTranslated {
+    id: u64,
+    text: String,
+    translated: String,
+}
+
+impl SuperChatTranslated {
+    fn from(raw: &JsonValue) -> JsonResult<Self> {
+        Ok(SuperChatTranslated {
+            // stringified in this command
+            id: string_u64(&raw["id"])?,
+            text: to(&raw["message"])?,
+            translated: to(&raw["message_jpn"])?,
+        })
+    }
+}
+
+// endregion
+
+// region: Moderation
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UserBlocked {
+    uid: u64,
+    uname: String,
+    operator: BlockOperator,
+    end_time: Option<i64>, // sec
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub enum BlockOperator {
+    Admin,
+    Anchor,
+    Other(u8),
+}
+
+impl BlockOperator {
+    fn from(value: &JsonValue) -> JsonResult<BlockOperator> {
+        let num: u8 = to(value)?;
+        Ok(match num {
+            1 => BlockOperator::Admin,
+            2 => BlockOperator::Anchor,
+            _ => BlockOperator::Other(num),
+        })
+    }
+}
+
+impl UserBlocked {
+    fn from(raw: &JsonValue) -> JsonResult<Self> {
+        Ok(UserBlocked {
+            uid: to(&raw["uid"])?,
+            uname: to(&raw["uname"])?,
+            operator: BlockOperator::from(&raw["operator"])?,
+            // not sent for permanent blocks
+            end_time: to::<Option<i64>>(&raw["block_end_time"])?.filter(|time| *time > 0),
+        })
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RoomSilence {
+    kind: String, // "level", "medal" or "member"
+    level: u32,
+    end_time: Option<i64>, // sec
+}
+
+impl RoomSilence {
+    fn from(raw: &JsonValue) -> JsonResult<Self> {
+        let second: i64 = to(&raw["second"])?;
+        Ok(RoomSilence {
+            kind: to(&raw["type"])?,
+            level: to(&raw["level"])?,
+            // -1 until turned off
+            end_time: if second > 0 { Some(second) } else { None },
+        })
+    }
+}
+
+// endregion
+
+// region: Lottery
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LotteryStart {
+    id: u64,
+    award_name: String,
+    award_count: u32,
+    danmaku: Option<String>,
+    gift: Option<LotteryGift>,
+    require_text: Option<String>,
+    duration: u32, // sec
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LotteryGift {
+    id: i32,
+    name: String,
+    count: u32,
+}
+
+impl LotteryStart {
+    fn from(raw: &JsonValue) -> JsonResult<Self> {
+        let gift_id: i32 = to(&raw["gift_id"])?;
+        Ok(LotteryStart {
+            id: to(&raw["id"])?,
+            award_name: to(&raw["award_name"])?,
+            award_count: to(&raw["award_num"])?,
+            danmaku: string_opt(&raw["danmu"])?,
+            gift: if gift_id == 0 { None } else {
+                Some(LotteryGift {
+                    id: gift_id,
+                    name: to(&raw["gift_name"])?,
+                    count: to(&raw["gift_num"])?,
+                })
+            },
+            require_text: string_opt(&raw["require_text"])?,
+            duration: to(&raw["max_time"])?,
+        })
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LotteryStatus {
+    id: u64,
+    status: u32,
+}
+
+impl LotteryStatus {
+    fn from(raw: &JsonValue) -> JsonResult<Self> {
+        Ok(LotteryStatus {
+            id: to(&raw["id"])?,
+            status: to(&raw["status"])?,
+        })
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LotteryAward {
+    id: u64,
+    award_name: String,
+    winners: Vec<LotteryWinner>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LotteryWinner {
+    uid: u64,
+    uname: String,
+}
+
+impl LotteryAward {
+    fn from(raw: &JsonValue) -> JsonResult<Self> {
+        let users: Vec<JsonValue> = to(&raw["award_users"])?;
+        Ok(LotteryAward {
+            id: to(&raw["id"])?,
+            award_name: to(&raw["award_name"])?,
+            winners: users.iter().map(|user| Ok(LotteryWinner {
+                uid: to(&user["uid"])?,
+                uname: to(&user["uname"])?,
+            })).collect::<JsonResult<_>>()?,
+        })
+    }
+}
+
+// endregion
+
+// region: VoiceJoin
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VoiceJoinCount {
+    apply_count: u32,
+    notify_count: u32,
+}
+
+impl VoiceJoinCount {
+    fn from(raw: &JsonValue) -> JsonResult<Self> {
+        Ok(VoiceJoinCount {
+            apply_count: to(&raw["apply_count"])?,
+            notify_count: to(&raw["notify_count"])?,
+        })
+    }
+}
+
+// only tells the applicant list changed, the list itself is not sent
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VoiceJoinList {
+    apply_count: u32,
+    category: u32,
+    refresh: bool,
+}
+
+impl VoiceJoinList {
+    fn from(raw: &JsonValue) -> JsonResult<Self> {
+        Ok(VoiceJoinList {
+            apply_count: to(&raw["apply_count"])?,
+            category: to(&raw["category"])?,
+            refresh: numbool(&raw["refresh"])?,
+        })
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VoiceJoinStatus {
+    kind: VoiceJoinStatusKind,
+    time: i64, // sec
+    // the following are empty when a link ends
+    uid: Option<u64>,
+    uname: Option<String>,
+    channel: Option<String>,
+    start_time: Option<i64>, // sec
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub enum VoiceJoinStatusKind {
+    End,
+    Start,
+    Other(u8),
+}
+
+impl VoiceJoinStatusKind {
+    fn from(value: &JsonValue) -> JsonResult<VoiceJoinStatusKind> {
+        let num: u8 = to(value)?;
+        Ok(match num {
+            0 => VoiceJoinStatusKind::End,
+            1 => VoiceJoinStatusKind::Start,
+            _ => VoiceJoinStatusKind::Other(num),
+        })
+    }
+}
+
+impl VoiceJoinStatus {
+    fn from(raw: &JsonValue) -> JsonResult<Self> {
+        Ok(VoiceJoinStatus {
+            kind: VoiceJoinStatusKind::from(&raw["status"])?,
+            time: to(&raw["current_time"])?,
+            uid: Some(to(&raw["uid"])?).filter(|uid| *uid != 0),
+            uname: string_opt(&raw["user_name"])?,
+            channel: string_opt(&raw["channel"])?,
+            start_time: Some(to(&raw["start_at"])?).filter(|time| *time != 0),
         })
     }
 }
@@ -470,7 +760,7 @@ pub struct RoomInfoDiff {
 
 // endregion
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 #[serde(tag = "type", content = "data")]
 pub enum Event {
     Popularity(u32),
@@ -479,10 +769,29 @@ pub enum Event {
     Danmaku(Danmaku),
     Interact(Interact),
     Gift(Gift),
+    GiftCombo(GiftCombo),
     GuardBuy(GuardBuy),
     SuperChat(SuperChat),
+    SuperChatDelete(Vec<u64>),
+    SuperChatTranslated(SuperChatTranslated),
     Views(Views),
 
+    UserBlocked(UserBlocked),
+    RoomSilenceOn(RoomSilence),
+    RoomSilenceOff,
+    AdminAdded(u64),
+    AdminRevoked(u64),
+    AdminList(Vec<u64>),
+
+    LotteryStart(LotteryStart),
+    LotteryStatus(LotteryStatus),
+    LotteryEnd(u64),
+    LotteryAward(LotteryAward),
+
+    VoiceJoinCount(VoiceJoinCount),
+    VoiceJoinList(VoiceJoinList),
+    VoiceJoinStatus(VoiceJoinStatus),
+
     RoomStat(RoomStat),
     RoomInfoChange(RoomInfoDiff),
 
@@ -499,41 +808,51 @@ pub enum Event {
 
 impl Event {
     pub fn parse<S: AsRef<str>>(raw: S) -> JsonResult<Event> {
-        let _raw = raw.as_ref();
+        Event::from_value(serde_json::from_str(raw.as_ref())?)
+    }
 
-        let raw: JsonValue = serde_json::from_str(_raw)?;
+    pub fn from_value(raw: JsonValue) -> JsonResult<Event> {
         let command: String = to(&raw["cmd"])?;
 
         Ok(match command.as_str() {
             "DANMU_MSG" => Event::Danmaku(Danmaku::from(&raw["info"])?),
             "INTERACT_WORD" => Event::Interact(Interact::from(&raw["data"])?),
             "SEND_GIFT" => Event::Gift(Gift::from(&raw["data"])?),
+            "COMBO_SEND" => Event::GiftCombo(GiftCombo::from(&raw["data"])?),
             "GUARD_BUY" => Event::GuardBuy(GuardBuy::from(&raw["data"])?),
-            "SUPER_CHAT_MESSAGE" => Event::SuperChat(SuperChat::from(&raw["data"], &raw["user_info"])?),
+            "SUPER_CHAT_MESSAGE" => Event::SuperChat(SuperChat::from(&raw["data"], &raw["data"]["user_info"])?),
+            "SUPER_CHAT_MESSAGE_DELETE" => Event::SuperChatDelete(to(&raw["data"]["ids"])?),
+            "SUPER_CHAT_MESSAGE_JPN" => Event::SuperChatTranslated(SuperChatTranslated::from(&raw["data"])?),
             "WATCHED_CHANGE" => Event::Views(Views::from(&raw["data"])?),
 
+            "ROOM_BLOCK_MSG" => Event::UserBlocked(UserBlocked::from(&raw["data"])?),
+            "ROOM_SILENT_ON" => Event::RoomSilenceOn(RoomSilence::from(&raw["data"])?),
+            "ROOM_SILENT_OFF" => Event::RoomSilenceOff,
+            // lowercase as sent
+            "room_admin_entrance" => Event::AdminAdded(to(&raw["uid"])?),
+            "ROOM_ADMIN_REVOKE" => Event::AdminRevoked(to(&raw["uid"])?),
+            "ROOM_ADMINS" => Event::AdminList(to(&raw["uids"])?),
+
+            "ANCHOR_LOT_START" => Event::LotteryStart(LotteryStart::from(&raw["data"])?),
+            "ANCHOR_LOT_CHECKSTATUS" => Event::LotteryStatus(LotteryStatus::from(&raw["data"])?),
+            "ANCHOR_LOT_END" => Event::LotteryEnd(to(&raw["data"]["id"])?),
+            "ANCHOR_LOT_AWARD" => Event::LotteryAward(LotteryAward::from(&raw["data"])?),
+
+            "VOICE_JOIN_ROOM_COUNT_INFO" => Event::VoiceJoinCount(VoiceJoinCount::from(&raw["data"])?),
+            "VOICE_JOIN_LIST" => Event::VoiceJoinList(VoiceJoinList::from(&raw["data"])?),
+            "VOICE_JOIN_STATUS" => Event::VoiceJoinStatus(VoiceJoinStatus::from(&raw["data"])?),
+
             "ROOM_REAL_TIME_MESSAGE_UPDATE" => Event::RoomStat(to(&raw["data"])?),
             "ROOM_CHANGE" => Event::RoomInfoChange(to(&raw["data"])?),
 
             "LIVE" => Event::LiveStart,
             "PREPARING" => Event::LiveEnd,
 
-            "ROOM_BLOCK_MSG"
-            | "SUPER_CHAT_MESSAGE_DELETE"
-            | "LIVE_INTERACTIVE_GAME"
-            | "COMBO_SEND"
+            "LIVE_INTERACTIVE_GAME"
             | "ENTRY_EFFECT"
-            | "SUPER_CHAT_MESSAGE_JPN"
             | "USER_TOAST_MSG"
             | "HOT_ROOM_NOTIFY"
-            | "SPECIAL_GIFT"
-            | "VOICE_JOIN_ROOM_COUNT_INFO"
-            | "VOICE_JOIN_LIST"
-            | "VOICE_JOIN_STATUS"
-            | "ANCHOR_LOT_CHECKSTATUS"
-            | "ANCHOR_LOT_START"
-            | "ANCHOR_LOT_END"
-            | "ANCHOR_LOT_AWARD" => Event::Unimplemented { raw },
+            | "SPECIAL_GIFT" => Event::Unimplemented { raw },
 
             "STOP_LIVE_ROOM_LIST"
             | "HOT_RANK_CHANGED"
@@ -556,33 +875,31 @@ impl Event {
             Package::Json(payload) => Event::parse(payload)?,
             Package::HeartbeatResponse(payload) => Event::Popularity(*payload),
             Package::InitResponse(payload) => InitResponse::parse(payload)?,
-            _ => unreachable!(),
+            Package::InitRequest(_) | Package::HeartbeatRequest => return Err(serde::de::Error::custom("sent by clients, not an event")),
+            // flattened
+            Package::Multi(_) => unreachable!(),
         })
     }
 
     pub fn from_raw<B: AsRef<[u8]>>(raw: B) -> Vec<Event> {
         let raw = raw.as_ref();
-        let mut events = Vec::new();
         match Package::decode(raw) {
-            Ok(package) => {
-                for flattened in package.flatten() {
-                    events.push(match Event::from_package(&flattened) {
-                        Ok(event) => event,
-                        Err(err) => Event::ParseError {
-                            raw: format!("{:?}", flattened),
-                            error: format!("{:?}", err),
-                        }
-                    })
-                }
-            }
-            Err(err) => {
-                events.push(Event::CodecError {
-                    raw: hex::encode(raw),
-                    error: format!("{:?}", err),
-                })
-            }
+            Ok(package) => Event::from_decoded(package),
+            Err(err) => vec![Event::CodecError {
+                raw: hex::encode(raw),
+                error: format!("{:?}", err),
+            }],
         }
-        events
+    }
+
+    pub fn from_decoded(package: Package) -> Vec<Event> {
+        package.flatten().into_iter().map(|flattened| match Event::from_package(&flattened) {
+            Ok(event) => event,
+            Err(err) => Event::ParseError {
+                raw: format!("{:?}", flattened),
+                error: format!("{:?}", err),
+            }
+        }).collect()
     }
 }
 
@@ -601,6 +918,123 @@ mod tests {
         }
     }
 
+    #[test]
+    fn event_serde_roundtrip() {
+        let raw = r##"{"cmd":"INTERACT_WORD","data":{"msg_type":1,"timestamp":1631676810,"uid":573732342,"uname":"进栈检票","fans_medal":{"medal_name":"滑稽果","is_lighted":1,"medal_level":18,"guard_level":0,"anchor_roomid":10308958,"target_id":13081892,"medal_color":"#424242","medal_color_border":13081892,"medal_color_start":13081892,"medal_color_end":13081892}}}"##;
+        let event = Event::parse(raw).unwrap();
+        let serialized = serde_json::to_string(&event).unwrap();
+        let deserialized: Event = serde_json::from_str(&serialized).unwrap();
+        assert!(matches!(deserialized, Event::Interact(Interact { kind: InteractKind::Enter, uid: 573732342, .. })));
+        assert_eq!(serde_json::to_string(&deserialized).unwrap(), serialized);
+    }
+
+    #[test]
+    fn gift_combo() {
+        let raw = r##"{"cmd":"COMBO_SEND","data":{"action":"投喂","batch_combo_id":"batch:gift:combo_id:573732342:13081892:31036:1631676810.1234","batch_combo_num":3,"combo_id":"gift:combo_id:573732342:13081892:31036:1631676810.1230","combo_num":5,"combo_total_coin":500,"gift_id":31036,"gift_name":"小花花","gift_num":0,"medal_info":{"medal_name":"","is_lighted":0,"medal_level":0,"guard_level":0,"anchor_roomid":0,"target_id":0,"medal_color":0,"medal_color_border":0,"medal_color_start":0,"medal_color_end":0},"r_uname":"滑稽果","ruid":13081892,"total_num":5,"uid":573732342,"uname":"进栈检票"}}"##;
+        match Event::parse(raw).unwrap() {
+            Event::GiftCombo(combo) => {
+                assert_eq!(combo.id, "gift:combo_id:573732342:13081892:31036:1631676810.1230");
+                assert_eq!((combo.uid, combo.gift_id, combo.gift_name.as_str()), (573732342, 31036, "小花花"));
+                assert_eq!((combo.total_count, combo.batch_count), (5, 3));
+                assert!(combo.medal.is_none());
+            },
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn super_chat_lifecycle() {
+        let raw = r##"{"cmd":"SUPER_CHAT_MESSAGE","data":{"background_bottom_color":"#2A60B2","background_color":"#EDF5FF","background_price_color":"#7497CD","id":7654321,"medal_info":{"anchor_roomid":10308958,"anchor_uname":"滑稽果","guard_level":3,"is_lighted":1,"medal_color":"#1a544b","medal_color_border":6809855,"medal_color_end":5414290,"medal_color_start":1725515,"medal_level":21,"medal_name":"滑稽果","target_id":13081892},"message":"こんにちは","price":30,"time":60,"ts":1631676810,"uid":573732342,"user_info":{"face":"https://i0.hdslb.com/bfs/face/member/noface.jpg","guard_level":3,"is_svip":0,"is_vip":0,"manager":0,"uname":"进栈检票","user_level":20}},"roomid":10308958}"##;
+        match Event::parse(raw).unwrap() {
+            Event::SuperChat(super_chat) => {
+                assert_eq!((super_chat.id, super_chat.price, super_chat.user.uid), (7654321, 30, 573732342));
+                assert_eq!((super_chat.background_color, super_chat.price_color), (0xEDF5FF, 0x7497CD));
+                assert!(matches!(super_chat.medal, Some(Medal { level: 21, color: 0x1a544b, .. })));
+            },
+            _ => unreachable!(),
+        }
+
+        let raw = r##"{"cmd":"SUPER_CHAT_MESSAGE_JPN","data":{"id":"7654321","message":"你好","message_jpn":"こんにちは","price":30,"uid":"573732342"},"roomid":"10308958"}"##;
+        match Event::parse(raw).unwrap() {
+            Event::SuperChatTranslated(translated) => {
+                assert_eq!((translated.id, translated.translated.as_str()), (7654321, "こんにちは"));
+            },
+            _ => unreachable!(),
+        }
+
+        let raw = r##"{"cmd":"SUPER_CHAT_MESSAGE_DELETE","data":{"ids":[7654321]},"roomid":10308958}"##;
+        assert!(matches!(&Event::parse(raw).unwrap(), Event::SuperChatDelete(ids) if ids == &[7654321]));
+    }
+
+    #[test]
+    fn moderation() {
+        let raw = r##"{"cmd":"ROOM_BLOCK_MSG","data":{"dmscore":30,"operator":1,"uid":573732342,"uname":"进栈检票"},"uid":"573732342","uname":"进栈检票"}"##;
+        assert!(matches!(Event::parse(raw).unwrap(), Event::UserBlocked(UserBlocked { uid: 573732342, operator: BlockOperator::Admin, end_time: None, .. })));
+        let raw = r##"{"cmd":"ROOM_BLOCK_MSG","data":{"operator":2,"uid":573732342,"uname":"进栈检票","block_end_time":1631680410}}"##;
+        assert!(matches!(Event::parse(raw).unwrap(), Event::UserBlocked(UserBlocked { operator: BlockOperator::Anchor, end_time: Some(1631680410), .. })));
+
+        let raw = r##"{"cmd":"ROOM_SILENT_ON","data":{"type":"level","level":20,"second":-1}}"##;
+        assert!(matches!(Event::parse(raw).unwrap(), Event::RoomSilenceOn(RoomSilence { level: 20, end_time: None, .. })));
+        let raw = r##"{"cmd":"ROOM_SILENT_OFF","data":{"type":"","level":0,"second":0}}"##;
+        assert!(matches!(Event::parse(raw).unwrap(), Event::RoomSilenceOff));
+
+        let raw = r##"{"cmd":"room_admin_entrance","msg":"系统提示：你已被主播设为房管","uid":573732342}"##;
+        assert!(matches!(Event::parse(raw).unwrap(), Event::AdminAdded(573732342)));
+        let raw = r##"{"cmd":"ROOM_ADMIN_REVOKE","msg":"撤销房管","uid":573732342}"##;
+        assert!(matches!(Event::parse(raw).unwrap(), Event::AdminRevoked(573732342)));
+        let raw = r##"{"cmd":"ROOM_ADMINS","uids":[573732342,13081892]}"##;
+        assert!(matches!(&Event::parse(raw).unwrap(), Event::AdminList(uids) if uids == &[573732342, 13081892]));
+    }
+
+    #[test]
+    fn lottery() {
+        let raw = r##"{"cmd":"ANCHOR_LOT_START","data":{"award_image":"","award_name":"情书","award_num":2,"cur_gift_num":0,"danmu":"我就是天选之人！","gift_id":0,"gift_name":"","gift_num":1,"gift_price":0,"goaway_time":180,"id":1234567,"join_type":0,"lot_status":0,"max_time":600,"require_text":"当前主播粉丝勋章至少1级","require_type":2,"require_value":1,"room_id":10308958,"status":1,"time":599}}"##;
+        match Event::parse(raw).unwrap() {
+            Event::LotteryStart(start) => {
+                assert_eq!((start.id, start.award_count, start.duration), (1234567, 2, 600));
+                assert_eq!(start.danmaku.as_deref(), Some("我就是天选之人！"));
+                assert!(start.gift.is_none());
+            },
+            _ => unreachable!(),
+        }
+        let raw = r##"{"cmd":"ANCHOR_LOT_START","data":{"award_name":"情书","award_num":1,"danmu":"","gift_id":31036,"gift_name":"小花花","gift_num":5,"id":1234568,"max_time":300,"require_text":""}}"##;
+        assert!(matches!(Event::parse(raw).unwrap(), Event::LotteryStart(LotteryStart { danmaku: None, gift: Some(LotteryGift { id: 31036, count: 5, .. }), require_text: None, .. })));
+
+        let raw = r##"{"cmd":"ANCHOR_LOT_CHECKSTATUS","data":{"id":1234567,"reject_reason":"","status":4,"uid":13081892}}"##;
+        assert!(matches!(Event::parse(raw).unwrap(), Event::LotteryStatus(LotteryStatus { id: 1234567, status: 4 })));
+        let raw = r##"{"cmd":"ANCHOR_LOT_END","data":{"id":1234567}}"##;
+        assert!(matches!(Event::parse(raw).unwrap(), Event::LotteryEnd(1234567)));
+
+        let raw = r##"{"cmd":"ANCHOR_LOT_AWARD","data":{"award_dont_popup":1,"award_image":"","award_name":"情书","award_num":2,"award_users":[{"uid":573732342,"uname":"进栈检票","face":"","level":20,"color":5805790,"num":1},{"uid":13081892,"uname":"滑稽果","face":"","level":10,"color":5805790,"num":1}],"id":1234567,"lot_status":2}}"##;
+        match Event::parse(raw).unwrap() {
+            Event::LotteryAward(award) => {
+                assert_eq!(award.id, 1234567);
+                let winners: Vec<_> = award.winners.iter().map(|winner| (winner.uid, winner.uname.as_str())).collect();
+                assert_eq!(winners, [(573732342, "进栈检票"), (13081892, "滑稽果")]);
+            },
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn voice_join() {
+        let raw = r##"{"cmd":"VOICE_JOIN_ROOM_COUNT_INFO","data":{"apply_count":3,"notify_count":1,"red_point":0,"room_id":10308958,"room_status":1,"root_status":1},"roomid":10308958}"##;
+        assert!(matches!(Event::parse(raw).unwrap(), Event::VoiceJoinCount(VoiceJoinCount { apply_count: 3, notify_count: 1 })));
+        let raw = r##"{"cmd":"VOICE_JOIN_LIST","data":{"apply_count":3,"category":1,"red_point":1,"refresh":1,"room_id":10308958},"roomid":10308958}"##;
+        assert!(matches!(Event::parse(raw).unwrap(), Event::VoiceJoinList(VoiceJoinList { apply_count: 3, category: 1, refresh: true })));
+
+        let raw = r##"{"cmd":"VOICE_JOIN_STATUS","data":{"channel":"voice320168","channel_type":"voice","current_time":1631676810,"guard":0,"head_pic":"","room_id":10308958,"start_at":1631676800,"status":1,"uid":573732342,"user_name":"进栈检票","web_share_link":""},"roomid":10308958}"##;
+        match Event::parse(raw).unwrap() {
+            Event::VoiceJoinStatus(status) => {
+                assert!(matches!(status.kind, VoiceJoinStatusKind::Start));
+                assert_eq!((status.uid, status.channel.as_deref(), status.start_time), (Some(573732342), Some("voice320168"), Some(1631676800)));
+            },
+            _ => unreachable!(),
+        }
+        let raw = r##"{"cmd":"VOICE_JOIN_STATUS","data":{"channel":"","channel_type":"voice","current_time":1631677810,"guard":0,"head_pic":"","room_id":10308958,"start_at":0,"status":0,"uid":0,"user_name":"","web_share_link":""},"roomid":10308958}"##;
+        assert!(matches!(Event::parse(raw).unwrap(), Event::VoiceJoinStatus(VoiceJoinStatus { kind: VoiceJoinStatusKind::End, uid: None, channel: None, .. })));
+    }
+
     #[test]
     fn test_string_color_to_u32() {
         assert_eq!(string_color_to_u32(&json_value!(42)).unwrap(), 42);
@@ -39,6 +39,7 @@ use log_config::log_config;
 
 mod api {
     use brapi_model::{*, prelude::*};
+    use livekit_feed::stream::HostInfo;
 
     #[derive(Clone, Debug, Serialize)]
     pub struct GetHostsInfo {
@@ -52,14 +53,6 @@ mod api {
         pub token: String,
     }
 
-    #[derive(Clone, Debug, Deserialize)]
-    pub struct HostInfo {
-        pub host: String,
-        pub port: u16,
-        pub ws_port: u16,
-        pub wss_port: u16,
-    }
-
     impl RestApi for GetHostsInfo {
         const BIZ: BizKind = BizKind::Live;
         const METHOD: RestApiRequestMethod = RestApiRequestMethod::BareGet;
@@ -74,13 +67,14 @@ use api::GetHostsInfo;
 // endregion
 
 use std::{path::PathBuf, future::Future};
+use futures_util::{StreamExt, future::join_all};
 use rand::{seq::SliceRandom, thread_rng as rng};
 
-use tokio::{spawn, signal, time::{sleep, Duration}, fs};
+use tokio::{spawn, select, signal, sync::watch, time::{sleep, timeout, Duration}, fs};
 
 use brapi_client::client::{Client, ClientRef};
 use livekit_feed_stor_raw::{Writer, RoomWriter};
-use livekit_feed::stream::{FeedStream, INIT_INTERVAL_MS, INIT_RETRY_INTERVAL_SEC, RETRY_INTERVAL_MS};
+use livekit_feed::stream::{AnyFeedStream, FeedStreamError, StreamConfig, Credential, Transport, Proxy, INIT_INTERVAL_MS, INIT_RETRY_INTERVAL_SEC, RETRY_INTERVAL_MS, CLOSE_TIMEOUT_SEC};
 
 // region: rec
 
@@ -97,29 +91,85 @@ macro_rules! unwrap_or_continue {
     };
 }
 
-fn rec(roomid: u32, api_client: ClientRef, room_writer: RoomWriter) -> impl Future<Output = ()> {
+fn rec(roomid: u32, transport: Transport, config: StreamConfig, mut shutdown: watch::Receiver<bool>, api_client: ClientRef, room_writer: RoomWriter) -> impl Future<Output = ()> {
     async move {
-        loop {
+        macro_rules! insert {
+            ($payload:expr) => {
+                if let Err(msg) = room_writer.insert_payload(&$payload).await {
+                    panic!("{}", msg);
+                }
+            };
+        }
+
+        'refresh: loop {
+            if *shutdown.borrow() {
+                return;
+            }
+
             let hosts_info = unwrap_or_continue!(
                 api_client.call(&GetHostsInfo { roomid }).await,
                 |err| log::warn!("[{: >10}] get hosts error {:?}", roomid, err)
             );
 
-            let host = hosts_info.host_list.choose(&mut rng()).expect("FATAL: empty host list");
-            let mut stream = unwrap_or_continue!(
-                FeedStream::connect_ws(&host.host, host.wss_port, roomid, api_client.uid().unwrap(), api_client.devid3().unwrap(), hosts_info.token).await,
-                |err| log::warn!("[{: >10}] error during connecting {:?}", roomid, err)
-            );
+            // network failures are retried on other hosts with the same token,
+            // a rejected init means the token is bad and has to be refreshed
+            let mut attempts = 0;
+            let mut stream = loop {
+                let host = hosts_info.host_list.choose(&mut rng()).expect("FATAL: empty host list");
+                // without access, connects as guest
+                let credential = match (api_client.uid(), api_client.devid3()) {
+                    (Some(uid), Some(devid3)) => Credential::with_access(uid, devid3, hosts_info.token.clone()),
+                    _ => Credential::guest(None, Some(hosts_info.token.clone())),
+                };
+                match AnyFeedStream::connect_with(&config, host, transport, roomid, credential).await {
+                    Ok(stream) => break stream,
+                    Err(FeedStreamError::InitRejected(code)) => {
+                        log::warn!("[{: >10}] init rejected with code {}, refreshing token", roomid, code);
+                        sleep(Duration::from_secs(INIT_RETRY_INTERVAL_SEC)).await;
+                        continue 'refresh;
+                    },
+                    Err(err) => {
+                        log::warn!("[{: >10}] error during connecting {:?}", roomid, err);
+                        sleep(Duration::from_secs(INIT_RETRY_INTERVAL_SEC)).await;
+                        attempts += 1;
+                        if attempts >= hosts_info.host_list.len() {
+                            continue 'refresh;
+                        }
+                    },
+                }
+            };
 
             log::info!("[{: >10}] open", roomid);
 
-            while let Some(payload) = stream.recv().await {
-                if let Err(msg) = room_writer.insert_payload(&payload).await {
-                    panic!("{}", msg);
+            // already logged one by one by the stream, only counted here so they do not pile up
+            let mut anomalies = 0;
+            loop {
+                let payload = select! {
+                    payload = stream.next() => payload,
+                    _ = shutdown.changed() => {
+                        if let Err(err) = stream.close().await {
+                            log::warn!("[{: >10}] error during closing {:?}", roomid, err);
+                        }
+                        // records what is still in flight
+                        let _ = timeout(Duration::from_secs(CLOSE_TIMEOUT_SEC), async {
+                            while let Some(Ok(payload)) = stream.next().await {
+                                insert!(payload);
+                            }
+                        }).await;
+                        log::info!("[{: >10}] close", roomid);
+                        return;
+                    },
+                };
+                anomalies += stream.take_diagnostics().len();
+                match payload {
+                    Some(Ok(payload)) => insert!(payload),
+                    Some(Err(FeedStreamError::IdleTimeout)) => log::warn!("[{: >10}] idle timeout", roomid),
+                    Some(Err(err)) => log::warn!("[{: >10}] error during receiving {:?}", roomid, err),
+                    None => break,
                 }
             }
 
-            log::info!("[{: >10}] close", roomid);
+            log::info!("[{: >10}] close ({} anomalies)", roomid, anomalies);
 
             sleep(Duration::from_millis(RETRY_INTERVAL_MS)).await;
         }
@@ -140,9 +190,15 @@ struct Args {
     /// log file path and name
     #[argh(option, short = 'l')]
     log_path: Option<PathBuf>,
-    /// access path
+    /// access path (connect as guest with reduced data if not set)
     #[argh(option, short = 'a')]
-    access_path: PathBuf,
+    access_path: Option<PathBuf>,
+    /// transport, one of tcp, ws and wss (default wss)
+    #[argh(option, default = "Transport::Wss")]
+    transport: Transport,
+    /// proxy for feed connections, http://[user:pass@]host:port or socks5://[user:pass@]host:port
+    #[argh(option)]
+    proxy: Option<Proxy>,
     /// set log level to debug (default is info)
     #[argh(switch)]
     log_debug: bool,
@@ -150,17 +206,28 @@ struct Args {
 
 #[tokio::main]
 async fn main() {
-    let Args { roomid_list, stor_path, log_path, access_path, log_debug } = argh::from_env();
+    let Args { roomid_list, stor_path, log_path, access_path, transport, proxy, log_debug } = argh::from_env();
     if let Some(log_path) = log_path {
         log4rs::init_config(log_config(log_path, log_debug)).expect("FATAL: error during init logger");
     }
-    let access = fs::read_to_string(access_path).await.unwrap();
     let (writer, writer_close) = Writer::open(stor_path).await.expect("FATAL: error during init feed raw storage");
-    let api_client = Client::with_access(access, None).expect("FATAL: access invaild");
+    let api_client = match access_path {
+        Some(access_path) => {
+            let access = fs::read_to_string(access_path).await.unwrap();
+            Client::with_access(access, None).expect("FATAL: access invaild")
+        },
+        None => Client::new(None).expect("FATAL: error during init api client"),
+    };
+    let config = StreamConfig { proxy, ..StreamConfig::default() };
+    let (shutdown, shutdown_rx) = watch::channel(false);
+    let mut recs = Vec::new();
     for roomid in roomid_list.split(',').map(|roomid| roomid.parse::<u32>().expect("FATAL: invaild roomid")) {
-        spawn(rec(roomid, api_client.clone(), writer.open_room(roomid)));
+        recs.push(spawn(rec(roomid, transport, config.clone(), shutdown_rx.clone(), api_client.clone(), writer.open_room(roomid))));
         sleep(Duration::from_millis(INIT_INTERVAL_MS)).await;
     }
     signal::ctrl_c().await.expect("FATAL: error during setting ctrl-c listener");
+    shutdown.send(true).expect("FATAL: all rec tasks exited");
+    // rooms that are not connected at the moment are not waited for
+    let _ = timeout(Duration::from_secs(CLOSE_TIMEOUT_SEC * 2), join_all(recs)).await;
     writer_close.wait_close().await.expect("FATAL: Error occurred during closing");
 }
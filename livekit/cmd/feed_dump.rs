@@ -1,7 +1,7 @@
 use std::{path::PathBuf, io::{Write, stdout}, fs::{self, OpenOptions}};
 use foundations::byterepr::ByteRepr;
-use livekit_feed::package::{Package, JsonPackage};
-use livekit_feed_stor_raw::{kvdump::{self, Row, KV}, crc32, Key};
+use livekit_feed::{package::{Package, JsonPackage}, dump::{Record, RecordInner}};
+use livekit_feed_stor_raw::{kvdump::{self, Row, KV}, crc32, Key, SESSION_SCOPE};
 
 /// export feed raw storage to jsonl file
 #[derive(argh::FromArgs)]
@@ -33,14 +33,6 @@ pub struct Args {
     filter_out_heartbeat_eq1: bool,
 }
 
-#[derive(serde::Serialize)]
-struct Record {
-    roomid: u32,
-    time: u64,
-    #[serde(flatten)]
-    inner: JsonPackage,
-}
-
 fn get_single_cmd(pkg: &JsonPackage) -> Option<&str> {
     if let JsonPackage::Json(json) = pkg {
         Some(json.as_object()?.get("cmd")?.as_str()?)
@@ -81,17 +73,36 @@ pub fn main(Args { raw_stor_path, export_path, roomid_list, file, from, to, filt
 
             match row.unwrap() {
                 Row::Hash(_) | Row::End => { },
+                // session records are not packages
+                Row::KV(KV { scope, .. }) if scope.as_ref() == SESSION_SCOPE => { },
                 Row::KV(KV { scope, key, value }) => {
                     let roomid = u32::from_be_bytes(scope.as_ref().try_into().unwrap());
                     let Key { time, hash } = Key::from_bytes(key.as_ref().try_into().unwrap());
-                    assert_eq!(hash, crc32(&value));
                     gate!(@opt roomid_list: { roomid_list.contains(&roomid) });
                     gate!(@opt from: { time > *from });
                     gate!(@opt to: { time < *to });
-                    let inner = Package::decode(&value).unwrap().to_json().unwrap();
-                    gate!(@bool filter_out_heartbeat_eq1: { !matches!(inner, JsonPackage::HeartbeatResponse(1)) });
-                    gate!(@opt filter_list: { get_single_cmd(&inner).map_or(true, |cmd| filter_list.contains(&cmd)) });
-                    let record = Record { roomid, time, inner };
+                    // bad rows are kept as error records, so that the export stays complete
+                    let checksum = crc32(&value);
+                    let record = if hash != checksum {
+                        eprintln!("[{: >10}] (feed-dump) {}: checksum mismatch", roomid, time);
+                        Record::codec_error(roomid, time, &value, format!("ChecksumMismatch {{ key: {:08x}, value: {:08x} }}", hash, checksum))
+                    } else {
+                        match Package::decode(&value).map(|package| package.to_json()) {
+                            Ok(Ok(inner)) => {
+                                gate!(@bool filter_out_heartbeat_eq1: { !matches!(inner, JsonPackage::HeartbeatResponse(1)) });
+                                gate!(@opt filter_list: { get_single_cmd(&inner).map_or(true, |cmd| filter_list.contains(&cmd)) });
+                                Record { roomid, time, inner: RecordInner::Package(inner) }
+                            },
+                            Ok(Err(err)) => {
+                                eprintln!("[{: >10}] (feed-dump) {}: json error {:?}", roomid, time, err);
+                                Record::parse_error(roomid, time, &value, format!("{:?}", err))
+                            },
+                            Err(err) => {
+                                eprintln!("[{: >10}] (feed-dump) {}: codec error {:?}", roomid, time, err);
+                                Record::codec_error(roomid, time, &value, format!("{:?}", err))
+                            },
+                        }
+                    };
                     serde_json::to_writer(&mut export_file, &record).unwrap();
                     writeln!(export_file).unwrap();
                 }